@@ -0,0 +1,750 @@
+use std::{ collections::HashMap, path::{ Path, PathBuf } };
+
+use derive_more::Display;
+use itertools::Itertools;
+use serde_json::{ Value, json };
+
+use crate::{
+    data::{
+        AssetIndex,
+        CollectibleSource,
+        CollectibleTarget,
+        CollectibleType,
+        Generated,
+        Ingredient,
+        Manifest,
+        RecipeKind,
+        VariantGroup,
+    },
+    datagen::glob_match,
+    error::Error,
+    output::describe,
+    package::collect_files,
+};
+
+/// The domain of codes that don't name one.
+const DEFAULT_DOMAIN: &str = "game";
+
+/// Asset folders of the base game that hold assets of the default domain, rather than a domain of
+/// their own name.
+const DEFAULT_DOMAIN_FOLDERS: [&str; 2] = ["survival", "creative"];
+
+/// The prefix of tag values that take their values from a variant group of an asset.
+const ASSET_PREFIX: &str = "@asset:";
+
+impl AssetIndex {
+    /// Index the itemtypes and blocktypes in `dirs`, each either an assets folder containing domain
+    /// folders, such as `game`, or a single domain folder. The base game's `survival` and
+    /// `creative` folders hold assets of the `game` domain.
+    pub fn load(&mut self, dirs: &[PathBuf]) -> Result<(), Error> {
+        for dir in dirs {
+            if is_domain(dir) {
+                let name = dir.file_name().map(|name| domain_name(&name.to_string_lossy()));
+                let name = name.unwrap_or_else(|| DEFAULT_DOMAIN.to_string());
+                self.domains.entry(name).or_default().push(dir.clone());
+                continue;
+            }
+            let mut entries = std::fs
+                ::read_dir(dir)
+                .map_err(Error::Io)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(Error::Io)?;
+            entries.sort();
+            for path in entries {
+                if is_domain(&path) {
+                    let name = domain_name(&path.file_name().unwrap_or_default().to_string_lossy());
+                    self.domains.entry(name).or_default().push(path);
+                }
+            }
+        }
+
+        // Every folder is known before indexing, as world properties may be in any of a domain's.
+        for (domain, paths) in self.domains.iter().sorted_by_key(|(domain, _)| *domain) {
+            let mut properties = WorldProperties::new(domain, &self.domains);
+            for path in paths {
+                for (folder, codes) in [
+                    ("itemtypes", &mut self.items),
+                    ("blocktypes", &mut self.blocks),
+                ] {
+                    let folder = path.join(folder);
+                    if !folder.is_dir() {
+                        continue;
+                    }
+                    let mut files = Vec::new();
+                    collect_files(&folder, &folder, &mut files)?;
+                    for (_, file) in files {
+                        for collectible in read_types(&file) {
+                            if collectible.enabled {
+                                for code in collectible.variants(&mut properties) {
+                                    codes.insert(format!("{domain}:{code}"));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Index a collectible generated in this run and written to `file`, so that recipes can use
+    /// its variants before it exists in any asset folder. Collectibles under `blocktypes` are
+    /// blocks, others are items.
+    pub fn insert_collectible(
+        &mut self,
+        file: &str,
+        collectible: &Value,
+        domain: Option<&str>
+    ) -> Result<(), Error> {
+        let collectible: CollectibleType = serde_json
+            ::from_value(collectible.clone())
+            .map_err(Error::Json)?;
+        if !collectible.enabled {
+            return Ok(());
+        }
+        let codes = if Path::new(file).iter().any(|folder| folder == "blocktypes") {
+            &mut self.blocks
+        } else {
+            &mut self.items
+        };
+        let domain = domain.unwrap_or(DEFAULT_DOMAIN);
+        let mut properties = WorldProperties::new(domain, &self.domains);
+        for code in collectible.variants(&mut properties) {
+            codes.insert(format!("{domain}:{code}"));
+        }
+        Ok(())
+    }
+
+    /// The states of a variant group of an itemtype or blocktype, referenced as
+    /// `game:itemtypes/resource/ingot.json#metal`. The file is looked up in every folder of the
+    /// domain. A group without any states, such as one loading them from a missing world properties
+    /// file, is an error.
+    pub fn variant_states(&self, reference: &str) -> Result<Vec<String>, Error> {
+        let unknown = || Error::UnknownAssetReference(reference.to_string());
+        let (location, group) = reference.split_once('#').ok_or_else(unknown)?;
+        let (domain, file) = location.split_once(':').unwrap_or((DEFAULT_DOMAIN, location));
+        let path = self.domains
+            .get(domain)
+            .into_iter()
+            .flatten()
+            .map(|dir| dir.join(file))
+            .find(|path| path.is_file())
+            .ok_or_else(unknown)?;
+
+        let mut properties = WorldProperties::new(domain, &self.domains);
+        let states = read_types(&path)
+            .iter()
+            .flat_map(|collectible| &collectible.variant_groups)
+            .find(|variant_group| variant_group.code == group)
+            .map(|variant_group| variant_group.states(&mut properties))
+            .ok_or_else(unknown)?;
+
+        if states.is_empty() {
+            return Err(Error::EmptyVariantGroup(reference.to_string()));
+        }
+        Ok(states)
+    }
+
+    /// Replace tag values referencing a variant group, such as
+    /// `@asset:game:itemtypes/resource/ingot.json#metal`, with the group's states. This covers the
+    /// tags of grammars and the variant groups of collectibles.
+    pub fn resolve_tags(&self, manifest: &mut Manifest) -> Result<(), Error> {
+        let grammar_tags = manifest.grammars.iter_mut().flat_map(|grammar| &mut grammar.tags);
+        let collectible_tags = manifest.collectibles
+            .iter_mut()
+            .flat_map(|collectible| &mut collectible.variant_groups);
+        for tag in grammar_tags.chain(collectible_tags) {
+            if !tag.values.iter().any(|value| value.starts_with(ASSET_PREFIX)) {
+                continue;
+            }
+            let mut values = Vec::new();
+            for value in &tag.values {
+                match value.strip_prefix(ASSET_PREFIX) {
+                    Some(reference) => values.extend(self.variant_states(reference)?),
+                    None => values.push(value.clone()),
+                }
+            }
+            tag.values = values;
+        }
+        Ok(())
+    }
+
+    /// Whether any indexed item or block of the ingredient's type matches its code. Wildcard codes
+    /// must match a variant whose wildcard part is neither skipped nor left out of the allowed
+    /// variants. Regular expression codes, starting with `@`, can't be checked and always match.
+    pub fn matches(&self, ingredient: &Ingredient) -> bool {
+        let codes = if ingredient.item_type.eq_ignore_ascii_case("block") {
+            &self.blocks
+        } else {
+            &self.items
+        };
+        let code = if ingredient.code.contains(':') {
+            ingredient.code.clone()
+        } else {
+            format!("{DEFAULT_DOMAIN}:{}", ingredient.code)
+        };
+        if code.contains(":@") {
+            return true;
+        }
+        if !code.contains('*') {
+            return codes.contains(&code);
+        }
+
+        let skipped = ingredient.skip_variants.as_deref().unwrap_or_default();
+        let allowed: Vec<&str> = ingredient.rest
+            .get("allowedVariants")
+            .and_then(Value::as_array)
+            .map(|allowed| allowed.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        codes.iter().any(|candidate| {
+            if !glob_match(&code, candidate) {
+                return false;
+            }
+            // Variants are only filtered by the part a single wildcard stands for.
+            let Some((prefix, suffix)) = code.split_once('*') else {
+                return true;
+            };
+            if suffix.contains('*') {
+                return true;
+            }
+            let variant = &candidate[prefix.len()..candidate.len() - suffix.len()];
+            !skipped.iter().any(|skipped| skipped == variant) &&
+                (allowed.is_empty() || allowed.contains(&variant))
+        })
+    }
+
+    /// Check that every item and block used by the recipes exists, printing each that doesn't.
+    pub fn validate_codes(&self, recipes: &[Generated]) -> Result<(), Error> {
+        let mut unknown = 0;
+        for recipe in recipes {
+            // Generic assets and patches don't describe ingredients.
+            if matches!(recipe.kind, RecipeKind::Generic | RecipeKind::Patch) {
+                continue;
+            }
+            let mut ingredients = Vec::new();
+            collect_ingredients(&recipe.recipe, &mut ingredients);
+            for ingredient in ingredients {
+                if !self.matches(&ingredient) {
+                    eprintln!(
+                        "Error: recipe {} uses unknown {} {} ({})",
+                        recipe.output,
+                        ingredient.item_type,
+                        ingredient.code,
+                        describe(recipe)
+                    );
+                    unknown += 1;
+                }
+            }
+        }
+
+        if unknown > 0 { Err(Error::UnknownCodes(unknown)) } else { Ok(()) }
+    }
+
+    /// Check that every domain-qualified texture and shape path in the generated output exists,
+    /// printing each that doesn't. Paths in domains without an asset folder, such as a misspelled
+    /// domain, are reported too.
+    pub fn validate_paths(&self, recipes: &[Generated]) -> Result<(), Error> {
+        let mut missing = 0;
+        for recipe in recipes {
+            let mut paths = Vec::new();
+            collect_asset_paths(&recipe.recipe, None, &mut paths);
+            for (kind, path) in paths {
+                let Some((domain, file)) = path.split_once(':') else {
+                    continue;
+                };
+                let Some(dirs) = self.domains.get(domain) else {
+                    eprintln!(
+                        "Error: recipe {} uses {kind} {path} in domain {domain}, which has no asset folder ({})",
+                        recipe.output,
+                        describe(recipe)
+                    );
+                    missing += 1;
+                    continue;
+                };
+                if !dirs.iter().any(|dir| kind.file(dir, file).is_file()) {
+                    eprintln!(
+                        "Error: recipe {} uses missing {kind} {path} ({})",
+                        recipe.output,
+                        describe(recipe)
+                    );
+                    missing += 1;
+                }
+            }
+        }
+
+        if missing > 0 { Err(Error::MissingAssets(missing)) } else { Ok(()) }
+    }
+}
+
+impl CollectibleType {
+    /// The codes of every variant of the type, without its domain.
+    pub fn variants(&self, properties: &mut WorldProperties) -> Vec<String> {
+        // Multiplicative groups form every combination of their states, while each state of an
+        // additive group forms a variant of its own.
+        let mut combinations: Vec<Vec<String>> = vec![Vec::new()];
+        let mut additive = Vec::new();
+        for group in &self.variant_groups {
+            let states = group.states(properties);
+            let combine = group.combine.as_deref().map(str::to_lowercase);
+            if matches!(combine.as_deref(), Some("add" | "additive")) {
+                additive.extend(states.into_iter().map(|state| vec![state]));
+                continue;
+            }
+            combinations = combinations
+                .into_iter()
+                .flat_map(|combination| {
+                    states.iter().map(move |state| {
+                        let mut combination = combination.clone();
+                        combination.push(state.clone());
+                        combination
+                    })
+                })
+                .collect();
+        }
+        combinations.extend(additive);
+
+        combinations
+            .into_iter()
+            .map(|states| std::iter::once(self.code.clone()).chain(states).join("-"))
+            .filter(|code| !self.skip_variants.iter().any(|skipped| glob_match(skipped, code)))
+            .filter(|code| {
+                self.allowed_variants.is_empty() ||
+                    self.allowed_variants.iter().any(|allowed| glob_match(allowed, code))
+            })
+            .collect()
+    }
+}
+
+impl CollectibleTarget {
+    /// Fill in the variant groups of the collectible, replacing the states of groups it already
+    /// has and adding the others.
+    pub fn generate(&self, static_props: &HashMap<String, Value>) -> Result<Value, Error> {
+        // Collectible files are only loaded when the manifest is read from a file.
+        let collectible = match &self.template {
+            CollectibleSource::Inline(collectible) => collectible,
+            CollectibleSource::File(path) => {
+                return Err(Error::UnloadedCollectible(path.clone()));
+            }
+        };
+        let mut collectible = collectible.clone();
+
+        let groups = collectible
+            .entry("variantgroups")
+            .or_insert_with(|| Value::Array(Vec::new()));
+        let Value::Array(groups) = groups else {
+            return Err(Error::InvalidRecipe {
+                output: self.output.clone(),
+                reason: "variantgroups is not a list".to_string(),
+            });
+        };
+        for tag in &self.variant_groups {
+            let states = Value::from(tag.resolve(static_props)?);
+            let group = groups
+                .iter_mut()
+                .filter_map(Value::as_object_mut)
+                .find(|group| group.get("code").and_then(Value::as_str) == Some(&tag.name));
+            match group {
+                Some(group) => {
+                    // Listed states take the place of any loaded from world properties.
+                    group.shift_remove("loadFromProperties");
+                    group.insert("states".to_string(), states);
+                }
+                None => groups.push(json!({ "code": tag.name, "states": states })),
+            }
+        }
+
+        Ok(Value::Object(collectible))
+    }
+}
+
+impl VariantGroup {
+    /// The states of the group, including those loaded from world properties.
+    fn states(&self, properties: &mut WorldProperties) -> Vec<String> {
+        let mut states = self.states.clone();
+        if let Some(name) = &self.load_from_properties {
+            states.extend(properties.get(name).iter().cloned());
+        }
+        states
+    }
+}
+
+/// The world properties of a domain, such as `block/rock`, loaded as they are needed.
+pub struct WorldProperties<'a> {
+    domain: &'a str,
+    domains: &'a HashMap<String, Vec<PathBuf>>,
+    states: HashMap<String, Vec<String>>,
+}
+
+impl<'a> WorldProperties<'a> {
+    pub fn new(domain: &'a str, domains: &'a HashMap<String, Vec<PathBuf>>) -> Self {
+        WorldProperties { domain, domains, states: HashMap::new() }
+    }
+
+    /// The codes of the variants a world properties file lists, looked up in every folder of its
+    /// domain.
+    fn get(&mut self, name: &str) -> &Vec<String> {
+        let (domain, file) = name.split_once(':').unwrap_or((self.domain, name));
+        let dirs = self.domains.get(domain).into_iter().flatten();
+        self.states.entry(name.to_string()).or_insert_with(|| {
+            let path = dirs
+                .map(|dir| dir.join("worldproperties").join(format!("{file}.json")))
+                .find(|path| path.is_file());
+            let Some(path) = path else {
+                eprintln!("Warning: could not find world properties {domain}:{file}");
+                return Vec::new();
+            };
+            let Some(value) = read_json5(&path) else {
+                return Vec::new();
+            };
+            value
+                .get("variants")
+                .and_then(Value::as_array)
+                .map(|variants| {
+                    variants
+                        .iter()
+                        .filter_map(|variant| variant.get("code").and_then(Value::as_str))
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default()
+        })
+    }
+}
+
+/// The domain of the assets in a domain folder, named after the folder.
+fn domain_name(folder: &str) -> String {
+    if DEFAULT_DOMAIN_FOLDERS.contains(&folder) {
+        DEFAULT_DOMAIN.to_string()
+    } else {
+        folder.to_string()
+    }
+}
+
+/// Whether `dir` is a domain folder, holding any of the asset folders we read.
+fn is_domain(dir: &Path) -> bool {
+    ["itemtypes", "blocktypes", "textures", "shapes"].iter().any(|folder| dir.join(folder).is_dir())
+}
+
+/// The kinds of asset files that generated output refers to by path.
+#[derive(Debug, Display, Clone, Copy)]
+enum AssetKind {
+    #[display("texture")]
+    Texture,
+    #[display("shape")]
+    Shape,
+}
+
+impl AssetKind {
+    /// The kind of asset the paths under a field refer to, judging by its name, such as
+    /// `stripsTexture` or `shape`.
+    fn of_field(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        if name.contains("texture") {
+            Some(AssetKind::Texture)
+        } else if name.contains("shape") {
+            Some(AssetKind::Shape)
+        } else {
+            None
+        }
+    }
+
+    /// The file a path refers to within a domain folder. Paths are written without an extension,
+    /// unless they name one.
+    fn file(self, dir: &Path, path: &str) -> PathBuf {
+        let (folder, extension) = match self {
+            AssetKind::Texture => ("textures", "png"),
+            AssetKind::Shape => ("shapes", "json"),
+        };
+        let file = dir.join(folder).join(path);
+        if file.extension().is_some() { file } else { file.with_extension(extension) }
+    }
+}
+
+/// Collect the domain-qualified paths in the fields of `value` that name textures or shapes, along
+/// with the kind of asset each refers to. Paths with variant placeholders or wildcards are skipped.
+fn collect_asset_paths<'a>(
+    value: &'a Value,
+    kind: Option<AssetKind>,
+    paths: &mut Vec<(AssetKind, &'a str)>
+) {
+    match value {
+        Value::String(path) => {
+            if
+                let Some(kind) = kind &&
+                path.contains(':') &&
+                !path.contains(['{', '*', '%'])
+            {
+                paths.push((kind, path));
+            }
+        }
+        Value::Object(fields) => {
+            for (name, field) in fields {
+                collect_asset_paths(field, AssetKind::of_field(name).or(kind), paths);
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                collect_asset_paths(value, kind, paths);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Read the collectible types in an asset file, which holds either one type or a list of them.
+fn read_types(path: &Path) -> Vec<CollectibleType> {
+    let types = match read_json5(path) {
+        Some(Value::Array(types)) => types,
+        Some(value) => vec![value],
+        None => Vec::new(),
+    };
+    types
+        .into_iter()
+        .filter_map(|value| serde_json::from_value(value).ok())
+        .collect()
+}
+
+/// Read a JSON5 asset file, warning when it can't be read, as game assets are outside our control.
+fn read_json5(path: &Path) -> Option<Value> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) => {
+            eprintln!("Warning: could not read asset {}: {err}", path.display());
+            return None;
+        }
+    };
+    match json5::from_str(text.trim_start_matches('\u{feff}')) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            eprintln!("Warning: could not parse asset {}: {err}", path.display());
+            None
+        }
+    }
+}
+
+/// Collect every item or block stack in a recipe: any object with an item or block `type` and a
+/// `code`.
+fn collect_ingredients(value: &Value, ingredients: &mut Vec<Ingredient>) {
+    match value {
+        Value::Object(fields) => {
+            let is_stack = fields
+                .get("type")
+                .and_then(Value::as_str)
+                .is_some_and(|kind| {
+                    kind.eq_ignore_ascii_case("item") || kind.eq_ignore_ascii_case("block")
+                });
+            if is_stack && let Ok(ingredient) = serde_json::from_value(value.clone()) {
+                ingredients.push(ingredient);
+            }
+            for field in fields.values() {
+                collect_ingredients(field, ingredients);
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                collect_ingredients(value, ingredients);
+            }
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::datagen::DataGen;
+
+    #[test]
+    fn match_wildcard_variants() {
+        let collectible: CollectibleType = serde_json
+            ::from_value(
+                json!({
+                "code": "hammer",
+                "variantgroups": [{ "code": "metal", "states": ["copper", "gold", "iron"] }],
+                "skipVariants": ["hammer-iron"],
+            })
+            )
+            .unwrap();
+        let domains = HashMap::new();
+        let mut properties = WorldProperties::new(DEFAULT_DOMAIN, &domains);
+        let mut index = AssetIndex::default();
+        for code in collectible.variants(&mut properties) {
+            index.items.insert(format!("game:{code}"));
+        }
+
+        let ingredient = |code: &str, skip: &[&str]| Ingredient {
+            item_type: "item".to_string(),
+            code: code.to_string(),
+            name: None,
+            skip_variants: Some(skip.iter().map(|skip| skip.to_string()).collect()),
+            rest: Default::default(),
+        };
+        assert!(index.matches(&ingredient("hammer-copper", &[])));
+        assert!(!index.matches(&ingredient("hammer-iron", &[])));
+        assert!(index.matches(&ingredient("game:hammer-*", &["copper"])));
+        assert!(!index.matches(&ingredient("hammer-*", &["copper", "gold"])));
+        assert!(!index.matches(&ingredient("hammer-cooper", &[])));
+    }
+
+    #[test]
+    fn generate_collectible_variant_groups() {
+        // A manifest may declare collectibles without any templates or grammars.
+        let manifest: Manifest = serde_json
+            ::from_value(
+                json!({
+                "static": { "metal": ["copper", "tinbronze"] },
+                "collectibles": [{
+                    "template": {
+                        "code": "shovel",
+                        "variantgroups": [
+                            { "code": "metal", "loadFromProperties": "block/metal" },
+                            { "code": "quality", "states": ["fine"] },
+                        ],
+                        "class": "ItemShovel",
+                    },
+                    "output": "itemtypes/shovel.json",
+                    "variantgroups": [
+                        { "name": "metal", "values": ["@metal", "gold"] },
+                        { "name": "handle", "values": ["wood"] },
+                    ],
+                }],
+            })
+            )
+            .unwrap();
+        let datagen = DataGen::new(&manifest);
+        assert!(datagen.generate().unwrap().is_empty());
+
+        let collectibles = datagen.collectibles().unwrap();
+        assert_eq!(collectibles.len(), 1);
+        assert_eq!(collectibles[0].0, "itemtypes/shovel.json");
+        assert_eq!(
+            collectibles[0].1,
+            json!({
+                "code": "shovel",
+                "variantgroups": [
+                    { "code": "metal", "states": ["copper", "tinbronze", "gold"] },
+                    { "code": "quality", "states": ["fine"] },
+                    { "code": "handle", "states": ["wood"] },
+                ],
+                "class": "ItemShovel",
+            })
+        );
+
+        // Recipes of the same run can use the generated variants.
+        let mut index = AssetIndex::default();
+        index.insert_collectible(&collectibles[0].0, &collectibles[0].1, Some("mymod")).unwrap();
+        assert!(index.items.contains("mymod:shovel-gold-fine-wood"));
+        assert_eq!(index.items.len(), 3);
+    }
+
+    #[test]
+    fn unloaded_collectible_is_an_error() {
+        let collectible: CollectibleTarget = serde_json
+            ::from_value(
+                json!({ "template": "itemtypes/shovel.json", "output": "shovel.json", "variantgroups": [] })
+            )
+            .unwrap();
+        let generated = collectible.generate(&HashMap::new());
+        assert!(matches!(generated, Err(Error::UnloadedCollectible(_))));
+    }
+
+    #[test]
+    fn empty_variant_group_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("vsgen-empty-group-{}", std::process::id()));
+        let itemtypes = dir.join("game").join("itemtypes");
+        std::fs::create_dir_all(&itemtypes).unwrap();
+        std::fs
+            ::write(
+                itemtypes.join("ingot.json"),
+                r#"{ code: "ingot", variantgroups: [
+                    { code: "metal", states: ["copper"] },
+                    { code: "rock", loadFromProperties: "block/nosuch" },
+                ] }"#
+            )
+            .unwrap();
+
+        let mut index = AssetIndex::default();
+        index.load(std::slice::from_ref(&dir)).unwrap();
+        let metal = index.variant_states("game:itemtypes/ingot.json#metal");
+        let rock = index.variant_states("game:itemtypes/ingot.json#rock");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(metal.unwrap(), ["copper"]);
+        assert!(matches!(rock, Err(Error::EmptyVariantGroup(_))));
+    }
+
+    #[test]
+    fn index_base_game_folders_as_game() {
+        let dir = std::env::temp_dir().join(format!("vsgen-base-game-{}", std::process::id()));
+        for folder in ["game", "survival"] {
+            std::fs::create_dir_all(dir.join(folder).join("itemtypes")).unwrap();
+        }
+        std::fs
+            ::write(
+                dir.join("survival").join("itemtypes").join("ingot.json"),
+                r#"{ code: "ingot", variantgroups: [{ code: "metal", loadFromProperties: "block/metal" }] }"#
+            )
+            .unwrap();
+        // World properties are in another folder of the domain than the itemtype using them.
+        std::fs::create_dir_all(dir.join("game").join("worldproperties").join("block")).unwrap();
+        std::fs
+            ::write(
+                dir.join("game").join("worldproperties").join("block").join("metal.json"),
+                r#"{ code: "metal", variants: [{ code: "copper" }, { code: "tin" }] }"#
+            )
+            .unwrap();
+
+        let mut index = AssetIndex::default();
+        index.load(std::slice::from_ref(&dir)).unwrap();
+        let metal = index.variant_states("game:itemtypes/ingot.json#metal");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(index.items.contains("game:ingot-copper"));
+        assert!(!index.domains.contains_key("survival"));
+        assert_eq!(index.domains["game"].len(), 2);
+        assert_eq!(metal.unwrap(), ["copper", "tin"]);
+    }
+
+    #[test]
+    fn report_missing_and_unknown_domain_paths() {
+        let dir = std::env::temp_dir().join(format!("vsgen-asset-paths-{}", std::process::id()));
+        let textures = dir.join("textures").join("block");
+        std::fs::create_dir_all(&textures).unwrap();
+        std::fs::write(textures.join("copper.png"), []).unwrap();
+
+        let mut index = AssetIndex::default();
+        index.domains.insert("game".to_string(), vec![dir.clone()]);
+        let recipe = |texture: &str| Generated::generic("test.json", json!({ "texture": texture }));
+        let found = index.validate_paths(&[recipe("game:block/copper")]);
+        let missing = index.validate_paths(&[recipe("game:block/tin"), recipe("gmae:block/copper")]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(found.is_ok());
+        assert!(matches!(missing, Err(Error::MissingAssets(2))));
+    }
+
+    #[test]
+    fn collect_texture_and_shape_paths() {
+        let recipe = json!({
+            "output": {
+                "type": "item",
+                "code": "nails",
+                "attributes": { "stripsTexture": "game:block/metal/ingot/copper" },
+            },
+            "shape": { "base": "mymod:item/nails", "alternates": [{ "base": "item/nails" }] },
+            "textures": { "metal": { "base": "game:block/metal/{metal}" } },
+        });
+        let mut paths = Vec::new();
+        collect_asset_paths(&recipe, None, &mut paths);
+        let paths: Vec<_> = paths
+            .into_iter()
+            .map(|(kind, path)| format!("{kind} {path}"))
+            .collect();
+
+        assert_eq!(paths, ["texture game:block/metal/ingot/copper", "shape mymod:item/nails"]);
+    }
+}
@@ -1,11 +1,16 @@
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{ Parser, Subcommand };
+
+use crate::data::{ Format, Layout, LineEnding, Style };
 
 /// Command line arguments for the application
 #[derive(Parser, Debug)]
 #[command(version, about, author, long_about = None)]
 pub struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Comma-separated paths to input files or directories to process
     #[arg(short = 'i', long = "input", value_parser)]
     paths: Vec<PathBuf>,
@@ -16,9 +21,71 @@ pub struct Args {
 
     #[arg(short, long, default_value = "dist")]
     dist: String,
+
+    /// Layout of the output files. Overrides the manifest's format.
+    #[arg(long, value_enum)]
+    style: Option<Style>,
+
+    /// Number of spaces to indent pretty printed output by. Overrides the manifest's format.
+    #[arg(long)]
+    indent: Option<usize>,
+
+    /// Indent pretty printed output with tabs instead of spaces.
+    #[arg(long)]
+    tabs: bool,
+
+    /// Whether to end output files with a newline. Overrides the manifest's format.
+    #[arg(long, value_name = "BOOL")]
+    trailing_newline: Option<bool>,
+
+    /// Line ending of the output files. Overrides the manifest's format.
+    #[arg(long, value_enum)]
+    line_ending: Option<LineEnding>,
+
+    /// Write JSON5 output, marked as generated by a header comment.
+    #[arg(long)]
+    json5: bool,
+
+    /// Precede each recipe in JSON5 output with a comment naming its grammar, template and tags.
+    #[arg(long)]
+    annotate: bool,
+
+    /// Where output files are placed within the dist directory. Overrides the manifest's layout.
+    #[arg(long, value_enum)]
+    layout: Option<Layout>,
+
+    /// The mod domain to place output files under with the mod layout. Overrides the manifest's
+    /// domain.
+    #[arg(long)]
+    domain: Option<String>,
+
+    /// Extracted game or mod asset folders to check every ingredient and output code against.
+    #[arg(long = "assets", value_name = "DIR")]
+    assets: Vec<PathBuf>,
+}
+
+/// Commands to run after generating recipes from the input files, if any.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Package the dist directory as an installable mod zip, with a generated modinfo.json.
+    Package {
+        /// Path to the config file with the mod's metadata.
+        #[arg(short, long, default_value = "vsgen.json")]
+        config: PathBuf,
+
+        /// Path to write the archive to. Defaults to <modid>_<version>.zip.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
 }
 
 impl Args {
+    /// Returns the command to run, if any.
+    #[inline]
+    pub fn command(&self) -> Option<&Command> {
+        self.command.as_ref()
+    }
+
     /// Returns the paths to the input files or directories to process.
     #[inline]
     pub fn paths(&self) -> &Vec<PathBuf> {
@@ -35,4 +102,36 @@ impl Args {
     pub fn dist(&self) -> &str {
         self.dist.as_str()
     }
+
+    /// Returns the layout given on the command line, if any.
+    #[inline]
+    pub fn layout(&self) -> Option<Layout> {
+        self.layout
+    }
+
+    /// Returns the asset folders to check codes against.
+    #[inline]
+    pub fn assets(&self) -> &Vec<PathBuf> {
+        &self.assets
+    }
+
+    /// Returns the mod domain given on the command line, if any.
+    #[inline]
+    pub fn domain(&self) -> Option<&str> {
+        self.domain.as_deref()
+    }
+
+    /// Returns the output format options given on the command line.
+    pub fn format(&self) -> Format {
+        Format {
+            style: self.style,
+            indent: self.indent,
+            // An explicit indent width asks for spaces, unless tabs were asked for too.
+            tabs: if self.tabs { Some(true) } else { self.indent.map(|_| false) },
+            trailing_newline: self.trailing_newline,
+            line_ending: self.line_ending,
+            json5: self.json5.then_some(true),
+            annotate: self.annotate.then_some(true),
+        }
+    }
 }
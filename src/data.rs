@@ -1,19 +1,193 @@
-use std::collections::HashMap;
+use std::{ collections::{ HashMap, HashSet }, path::PathBuf };
 use derive_more::Display;
+use indexmap::IndexMap;
 use serde::{ Deserialize, Serialize };
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Manifest {
-    /// The output file name.
-    pub output: String,
+    /// The path the manifest was read from.
+    #[serde(skip)]
+    pub path: PathBuf,
+    /// The path of the manifest relative to the input it was found through, with `/` separators,
+    /// such as `tools/shovel.json`. Names the manifest in generated files, so they don't depend on
+    /// where the inputs are.
+    #[serde(skip)]
+    pub name: String,
+    /// The output file name, relative to the dist directory. Tag placeholders such as `%metal%`
+    /// route each recipe to a file named after its tag values. Only needed when a grammar doesn't set
+    /// its own.
+    #[serde(default)]
+    pub output: Option<String>,
     /// Static properties that can be referenced through tags in template recipes.
     #[serde(rename = "static", default)]
     pub static_props: HashMap<String, serde_json::Value>,
     /// A list of templates each representing the Vintage Story recipe structure.
+    #[serde(default)]
     pub templates: Vec<Template>,
     /// A list of grammars to apply to the template recipe to generate final recipes. Each grammar
     /// has the potential to create multiple recipes representing variants of the same recipe.
+    #[serde(default)]
     pub grammars: Vec<Grammar>,
+    /// The order to write the generated recipes in. Recipes are written in the order their
+    /// grammars generate them if omitted.
+    #[serde(default)]
+    pub sort: Option<SortOrder>,
+    /// How the output file is laid out.
+    #[serde(default)]
+    pub format: Format,
+    /// Where output files are placed within the dist directory.
+    #[serde(default)]
+    pub layout: Option<Layout>,
+    /// The mod domain to place output files under with the mod layout.
+    #[serde(default)]
+    pub domain: Option<String>,
+    /// Translations of tag values per locale, used when rendering lang values for that locale.
+    #[serde(default)]
+    pub translations: HashMap<String, Translations>,
+    /// Itemtypes and blocktypes to write with variant groups taken from static properties, so
+    /// they list the same states as the recipes for them.
+    #[serde(default)]
+    pub collectibles: Vec<CollectibleTarget>,
+}
+
+/// An itemtype or blocktype written with some of its variant groups generated.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CollectibleTarget {
+    /// The itemtype or blocktype to start from.
+    pub template: CollectibleSource,
+    /// The file to write the collectible to, relative to the dist directory, such as
+    /// `itemtypes/tool/shovel.json`.
+    pub output: String,
+    /// The variant groups to create or replace the states of, named by their code. Values are
+    /// resolved like tag values.
+    #[serde(rename = "variantgroups")]
+    pub variant_groups: Vec<Tag>,
+}
+
+/// The itemtype or blocktype a [`CollectibleTarget`] starts from.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum CollectibleSource {
+    /// A collectible declared in the manifest.
+    Inline(serde_json::Map<String, serde_json::Value>),
+    /// A path to an existing JSON or JSON5 file, relative to the manifest. Replaced by its contents
+    /// when the manifest is loaded.
+    File(PathBuf),
+}
+
+/// Translations of tag values for a single locale, keyed by the untranslated value.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Translations {
+    /// A table declared in the manifest.
+    Table(HashMap<String, String>),
+    /// A path to a JSON file containing the table, relative to the manifest. Replaced by its table
+    /// when the manifest is loaded.
+    File(PathBuf),
+}
+
+/// Where output files are placed within the dist directory.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum Layout {
+    /// Directly at their output path.
+    Flat,
+    /// At their output path within the assets folder of the mod domain, under the recipes folder
+    /// for their recipe kind.
+    Mod,
+}
+
+/// How an output file is laid out. Options given on the command line override the manifest's,
+/// and options neither sets fall back to pretty printing with an indent of 4 spaces, a trailing
+/// newline and LF line endings.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Format {
+    /// Whether to write each recipe on a single line or across indented lines.
+    pub style: Option<Style>,
+    /// The number of spaces to indent pretty printed output by.
+    pub indent: Option<usize>,
+    /// Whether to indent pretty printed output with tabs instead of spaces.
+    pub tabs: Option<bool>,
+    /// Whether to end the file with a newline.
+    pub trailing_newline: Option<bool>,
+    /// The line ending to use.
+    pub line_ending: Option<LineEnding>,
+    /// Whether to write JSON5, starting with a comment that marks the file as generated.
+    pub json5: Option<bool>,
+    /// Whether to precede each recipe with a comment naming its grammar, template and tag values.
+    /// Only applies to JSON5 output.
+    pub annotate: Option<bool>,
+}
+
+/// The layout of recipes in an output file.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum Style {
+    /// One recipe per line.
+    Compact,
+    /// Every field on its own indented line.
+    Pretty,
+}
+
+/// The line ending of an output file.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+/// An order to sort generated recipes by.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum SortOrder {
+    /// Sort by the output code of each recipe.
+    Output,
+    /// Sort by the tag values each recipe was generated from, in the order the tags are declared.
+    Tags,
+}
+
+/// Lang entries by locale, then by key. Keys and values may contain tag placeholders, which can be
+/// piped through filters such as `%metal|title%`.
+pub type Lang = IndexMap<String, IndexMap<String, String>>;
+
+/// The configuration used to package the dist directory as a mod.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PackageConfig {
+    /// The metadata written to `modinfo.json`.
+    #[serde(rename = "modinfo")]
+    pub mod_info: ModInfo,
+    /// Folders of static assets to add to the archive, such as textures and shapes, relative to the
+    /// config. The contents of each folder are added at the root of the archive.
+    #[serde(default)]
+    pub include: Vec<PathBuf>,
+}
+
+/// The metadata of a Vintage Story mod, as written to `modinfo.json`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ModInfo {
+    /// The type of mod. Mods made of assets only are `content` mods.
+    #[serde(rename = "type", default = "ModInfo::default_type")]
+    pub mod_type: String,
+    pub modid: String,
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Maps the ids of required mods, including `game`, to their minimum versions.
+    #[serde(default)]
+    pub dependencies: IndexMap<String, String>,
+    #[serde(flatten)]
+    pub rest: IndexMap<String, serde_json::Value>,
+}
+
+impl ModInfo {
+    fn default_type() -> String {
+        "content".to_string()
+    }
 }
 
 /// A template recipe that can be used to generate multiple variants of a recipe using short-hand
@@ -21,36 +195,257 @@ pub struct Manifest {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Template {
     pub name: String,
+    /// The kind of recipe the template describes.
+    #[serde(default)]
+    pub kind: RecipeKind,
+    /// Lang entries to generate for each tag combination, by locale.
+    #[serde(default)]
+    pub lang: Lang,
+    /// Recipes of the game or other mods that the template's recipes replace. A patch disabling
+    /// each of them is generated alongside the recipes.
+    #[serde(default)]
+    pub supersedes: Vec<Superseded>,
+    /// Mods the template's recipes only apply with.
+    #[serde(rename = "dependsOn", default)]
+    pub depends_on: Vec<DependsOn>,
+    /// The body of the recipe, which is deserialized into the model for its kind once the grammar
+    /// has been applied. Holds every field of the template other than the ones above.
     #[serde(flatten)]
-    pub recipe: Recipe,
+    pub recipe: serde_json::Map<String, serde_json::Value>,
 }
 
-/// A crafting recipe for Vintage Story.
+/// A recipe replaced by a template, identified by the file it is loaded from. Tag placeholders in
+/// the file are resolved for each tag combination.
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Recipe {
+pub struct Superseded {
+    /// The file the recipe is loaded from, such as `game:recipes/grid/tool/shovel.json`.
+    pub file: String,
+    /// The index of the recipe, for files that contain a list of recipes.
+    #[serde(default)]
+    pub index: Option<usize>,
+}
+
+/// A condition on another mod being installed, or with `invert`, not being installed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DependsOn {
+    pub modid: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub invert: bool,
+}
+
+/// The kinds of Vintage Story recipes a template can describe.
+#[derive(Serialize, Deserialize, Debug, Display, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RecipeKind {
+    /// A crafting grid recipe.
+    #[default]
+    #[display("grid")]
+    Grid,
+    /// An anvil recipe with layers of 16x16 voxels.
+    #[display("smithing")]
+    Smithing,
+    /// A knapping recipe with a single layer of 10x10 voxels.
+    #[display("knapping")]
+    Knapping,
+    /// A clay forming recipe with layers of 16x16 voxels.
+    #[display("clayforming")]
+    Clayforming,
+    /// A barrel recipe mixing items and liquids over time.
+    #[display("barrel")]
+    Barrel,
+    /// A cooking pot recipe.
+    #[display("cooking")]
+    Cooking,
+    /// A metal alloy recipe with ingredient ratio ranges.
+    #[display("alloy")]
+    Alloy,
+    /// Any other asset file, such as an itemtype, blocktype or entity. The body is kept as plain
+    /// JSON and written exactly as the grammar leaves it.
+    #[display("generic")]
+    Generic,
+    /// A JSON patch operation that modifies an asset of the game or another mod instead of adding
+    /// a new one.
+    #[display("patch")]
+    Patch,
+}
+
+/// A Vintage Story recipe of any kind.
+#[derive(Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Recipe {
+    Grid(GridRecipe),
+    Voxel(VoxelRecipe),
+    Barrel(BarrelRecipe),
+    Cooking(CookingRecipe),
+    Alloy(AlloyRecipe),
+    Generic(serde_json::Value),
+    Patch(PatchOperation),
+}
+
+/// A crafting grid recipe for Vintage Story.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GridRecipe {
     /// The ingredient pattern for the recipe.
     #[serde(rename = "ingredientPattern")]
     pub pattern: String,
-    pub ingredients: HashMap<char, Ingredient>,
+    pub ingredients: IndexMap<char, Ingredient>,
+    /// The number of columns in the pattern. Computed from the pattern when omitted or `0`.
+    #[serde(default)]
     pub width: u8,
+    /// The number of rows in the pattern. Computed from the pattern when omitted or `0`.
+    #[serde(default)]
     pub height: u8,
     pub output: Ingredient,
     #[serde(flatten)]
-    pub rest: HashMap<String, serde_json::Value>,
+    pub rest: IndexMap<String, serde_json::Value>,
 }
 
-impl Recipe {
-    pub fn write<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
-        // Serialize the recipe to JSON and write it to the writer
-        let json = serde_json::to_string(self)?;
-        writeln!(writer, "{json}")
-    }
+/// A smithing, knapping or clay forming recipe, shaped by layers of voxels.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VoxelRecipe {
+    /// The material the recipe is worked from.
+    pub ingredient: Ingredient,
+    /// Layers of rows, from the bottom up, where `#` is a filled voxel and `_` is empty.
+    pub pattern: Vec<Vec<String>>,
+    pub output: Ingredient,
+    #[serde(flatten)]
+    pub rest: IndexMap<String, serde_json::Value>,
+}
+
+/// A barrel recipe, which turns items and liquids into an output once sealed for long enough.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BarrelRecipe {
+    pub code: String,
+    /// The number of in-game hours the barrel must be sealed for.
+    pub seal_hours: f64,
+    /// The item and liquid ingredients, where liquids specify `litres`.
+    pub ingredients: Vec<Ingredient>,
+    pub output: Ingredient,
+    #[serde(flatten)]
+    pub rest: IndexMap<String, serde_json::Value>,
+}
+
+/// A cooking pot recipe.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CookingRecipe {
+    pub code: String,
+    pub ingredients: Vec<CookingIngredient>,
+    #[serde(flatten)]
+    pub rest: IndexMap<String, serde_json::Value>,
+}
+
+/// A slot in a cooking pot recipe, filled by any of its valid stacks.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CookingIngredient {
+    pub code: String,
+    pub valid_stacks: Vec<Ingredient>,
+    pub min_quantity: u32,
+    pub max_quantity: u32,
+    #[serde(flatten)]
+    pub rest: IndexMap<String, serde_json::Value>,
+}
+
+/// A metal alloy recipe.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AlloyRecipe {
+    pub ingredients: Vec<AlloyIngredient>,
+    pub output: Ingredient,
+    #[serde(flatten)]
+    pub rest: IndexMap<String, serde_json::Value>,
+}
+
+/// A Vintage Story JSON patch operation, applied to another asset when the game loads.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchOperation {
+    pub op: PatchOp,
+    /// The location to patch. Written as a dot path, such as `ingredients.P.code`, and converted
+    /// to a JSON Pointer, unless it already is one.
+    pub path: String,
+    /// The asset to patch, such as `game:recipes/grid/tool/shovel.json`.
+    pub file: String,
+    /// The value to add or replace with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+    /// The location to move or copy from, written like `path`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_path: Option<String>,
+    /// Mods the patch is only applied with.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<DependsOn>,
+    #[serde(flatten)]
+    pub rest: IndexMap<String, serde_json::Value>,
+}
+
+/// The operations a JSON patch can perform.
+#[derive(Serialize, Deserialize, Debug, Display, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PatchOp {
+    #[display("add")]
+    Add,
+    /// Add each element of an array value.
+    #[display("addeach")]
+    AddEach,
+    /// Add the value, merging it into an existing object or array.
+    #[display("addmerge")]
+    AddMerge,
+    #[display("remove")]
+    Remove,
+    #[display("replace")]
+    Replace,
+    #[display("copy")]
+    Copy,
+    #[display("move")]
+    Move,
+}
+
+/// A metal in an alloy, and the range of its share of the melt.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AlloyIngredient {
+    #[serde(rename = "type")]
+    pub item_type: String,
+    pub code: String,
+    pub minratio: f64,
+    pub maxratio: f64,
+    #[serde(flatten)]
+    pub rest: IndexMap<String, serde_json::Value>,
+}
+
+/// A recipe generated by a grammar, ready to be written.
+#[derive(Debug)]
+pub struct Generated {
+    /// The kind of recipe that was generated.
+    pub kind: RecipeKind,
+    /// The name of the grammar that generated the recipe, if it has one.
+    pub grammar: Option<String>,
+    /// The name of the template the recipe was generated from.
+    pub template: String,
+    /// The file to write the recipe to, relative to the dist directory.
+    pub file: String,
+    /// The code of the recipe's output, or the code of the recipe if it has no output.
+    pub output: String,
+    /// The tag names and values the recipe was generated from.
+    pub tags: Vec<(String, String)>,
+    /// Mods the recipe only applies with. Patches carry their conditions themselves, so this is
+    /// only set for other kinds.
+    pub depends_on: Vec<DependsOn>,
+    /// The recipe as JSON, with its fields in the order the template declared them.
+    pub recipe: serde_json::Value,
 }
 
 /// A grammar is a set of rules that can be applied to a template recipe to generate multiple
 /// variants of the same recipe.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Grammar {
+    /// The name of the grammar, used to describe where generated recipes came from.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The output file for recipes generated by this grammar, overriding the manifest's output.
+    /// Supports the same tag placeholders.
+    #[serde(default)]
+    pub output: Option<String>,
     /// The name(s) of the [`Template`] to apply the grammar to.
     pub template: Option<OneOrMany<String>>,
     /// Tags mapping tag names to their values.
@@ -61,10 +456,41 @@ pub struct Grammar {
     /// Properties to create or replace in the recipe.
     #[serde(default)]
     pub modify: Vec<Modify>,
+    /// Operations on the crafting grid that keep the pattern and ingredients consistent.
+    #[serde(default)]
+    pub grid: Vec<GridOp>,
+    /// Transformed copies of the pattern to emit alongside each generated recipe.
+    #[serde(default)]
+    pub transforms: Vec<PatternTransform>,
+    /// Operations on the voxel layers of smithing, knapping and clay forming recipes.
+    #[serde(default)]
+    pub voxels: Vec<VoxelOp>,
+    /// Operations applied after tags have been substituted.
+    #[serde(default)]
+    pub finalize: Finalize,
+    /// Lang entries to generate for each tag combination, by locale, in addition to those of the
+    /// template.
+    #[serde(default)]
+    pub lang: Lang,
+    /// Mods the generated recipes only apply with, in addition to those of the template.
+    #[serde(rename = "dependsOn", default)]
+    pub depends_on: Vec<DependsOn>,
     #[serde(flatten)]
     pub rest: HashMap<String, serde_json::Value>,
 }
 
+/// Removals and modifications applied to a recipe after its tags have been substituted, so they can
+/// see the final values.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Finalize {
+    /// Properties to remove from the recipe.
+    #[serde(default)]
+    pub remove: Vec<Remove>,
+    /// Properties to create or replace in the recipe.
+    #[serde(default)]
+    pub modify: Vec<Modify>,
+}
+
 /// An ingredient in a recipe.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -72,11 +498,68 @@ pub struct Ingredient {
     #[serde(rename = "type")]
     pub item_type: String,
     pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skip_variants: Option<Vec<String>>,
+    #[serde(flatten)]
+    pub rest: IndexMap<String, serde_json::Value>,
+}
+
+/// An itemtype or blocktype asset, reduced to the fields that determine the codes of its variants.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectibleType {
+    pub code: String,
+    /// Whether the game loads the type at all.
+    #[serde(default = "CollectibleType::default_enabled")]
+    pub enabled: bool,
+    /// The groups of states the variants are formed from.
+    #[serde(rename = "variantgroups", default)]
+    pub variant_groups: Vec<VariantGroup>,
+    /// Variant codes to leave out, which may contain wildcards.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub skip_variants: Vec<String>,
+    /// Variant codes to keep, leaving out all others, which may contain wildcards.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_variants: Vec<String>,
     #[serde(flatten)]
-    pub rest: HashMap<String, serde_json::Value>,
+    pub rest: IndexMap<String, serde_json::Value>,
+}
+
+impl CollectibleType {
+    fn default_enabled() -> bool {
+        true
+    }
+}
+
+/// A group of states that variants of a collectible choose from, such as its metal.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct VariantGroup {
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub states: Vec<String>,
+    /// A world properties file to take the states from, such as `block/rock`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load_from_properties: Option<String>,
+    /// How the states combine with the other groups: `multiply` (the default), or `add`, where
+    /// each state forms a variant on its own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub combine: Option<String>,
+    #[serde(flatten)]
+    pub rest: IndexMap<String, serde_json::Value>,
+}
+
+/// The codes of every item and block variant found in a local asset directory, prefixed with
+/// their domain, such as `game:ingot-copper`.
+#[derive(Debug, Default)]
+pub struct AssetIndex {
+    pub items: HashSet<String>,
+    pub blocks: HashSet<String>,
+    /// The folders of each indexed domain, in the order they were given. The base game alone
+    /// spreads the `game` domain across several folders.
+    pub domains: HashMap<String, Vec<PathBuf>>,
 }
 
 /// Maps a tag name to its values in a recipe.
@@ -88,9 +571,36 @@ pub struct Tag {
     pub values: Vec<String>,
 }
 
-/// A path to a property in the recipe to remove.
+/// A property in the recipe to remove.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Remove {
+    /// Remove the property at the given path.
+    Path(DotPath),
+    /// Remove every entry of the array or object at `path` that matches the predicate.
+    Where {
+        /// The path to the array or object to filter.
+        path: DotPath,
+        /// The predicate an entry must match to be removed.
+        #[serde(rename = "where")]
+        predicate: Predicate,
+    },
+    /// Remove the property at `path` if the recipe matches the condition.
+    When {
+        /// The path to the property to remove.
+        path: DotPath,
+        /// The predicate the whole recipe must match.
+        #[serde(rename = "if")]
+        condition: Predicate,
+    },
+}
+
+/// Maps paths relative to an entry to the values they must match.
+/// - String values are matched as globs, where `*` matches any run of characters and `?` matches
+///   a single character.
+/// - Any other value must be equal.
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Remove(pub DotPath);
+pub struct Predicate(pub HashMap<String, serde_json::Value>);
 
 /// A modification to a property in the recipe.
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -99,6 +609,91 @@ pub struct Modify {
     pub path: DotPath,
     /// The new value for the property.
     pub value: serde_json::Value,
+    /// The predicate the whole recipe must match for the modification to apply.
+    #[serde(rename = "if", default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<Predicate>,
+}
+
+/// An operation on the crafting grid of a recipe. Each operation updates the `ingredientPattern`,
+/// `ingredients`, `width` and `height` together.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum GridOp {
+    /// Rename an ingredient key everywhere it is used.
+    RenameKey {
+        from: char,
+        to: char,
+    },
+    /// Swap the cells occupied by two ingredient keys.
+    SwapKeys {
+        a: char,
+        b: char,
+    },
+    /// Place an ingredient key, or `_` for an empty cell, at a zero-based row and column.
+    SetCell {
+        row: usize,
+        col: usize,
+        key: char,
+    },
+    /// Add empty rows and columns around the pattern.
+    #[serde(rename_all = "camelCase")]
+    PadPattern {
+        #[serde(default)]
+        top: usize,
+        #[serde(default)]
+        bottom: usize,
+        #[serde(default)]
+        left: usize,
+        #[serde(default)]
+        right: usize,
+    },
+}
+
+/// A transformation of a crafting grid pattern or a voxel layer.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum PatternTransform {
+    /// Mirror the pattern horizontally, swapping left and right.
+    Mirror,
+    /// Flip the pattern vertically, swapping top and bottom.
+    Flip,
+    /// Rotate the pattern 90 degrees clockwise.
+    Rotate90,
+    /// Rotate the pattern 180 degrees.
+    Rotate180,
+    /// Rotate the pattern 270 degrees clockwise.
+    Rotate270,
+}
+
+/// An operation on the layers of a voxel pattern.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum VoxelOp {
+    /// Mirror, flip or rotate layers.
+    Transform {
+        transform: PatternTransform,
+        /// The zero-based layers to transform, or every layer if omitted.
+        #[serde(default)]
+        layers: Option<Vec<usize>>,
+    },
+    /// Shift layers by `x` columns and `z` rows, keeping the size of each layer.
+    Translate {
+        #[serde(default)]
+        x: isize,
+        #[serde(default)]
+        z: isize,
+        /// The zero-based layers to shift, or every layer if omitted.
+        #[serde(default)]
+        layers: Option<Vec<usize>>,
+    },
+    /// Fill every voxel that is filled in the given layers.
+    Union {
+        pattern: Vec<Vec<String>>,
+    },
+    /// Clear every voxel that is filled in the given layers.
+    Subtract {
+        pattern: Vec<Vec<String>>,
+    },
 }
 
 /// A path to a property in the data structure.
@@ -109,7 +704,7 @@ pub struct Modify {
 pub struct DotPath(pub String);
 
 impl DotPath {
-    pub fn tokenize(&self) -> Vec<DotToken> {
+    pub fn tokenize(&self) -> Vec<DotToken<'_>> {
         self.0
             .split('.')
             .map(|s| {
@@ -124,6 +719,19 @@ impl DotPath {
             })
             .collect()
     }
+
+    /// Convert the path to a [JSON Pointer](https://datatracker.ietf.org/doc/html/rfc6901).
+    ///
+    /// Wildcards have no JSON Pointer equivalent and are written as a literal `*` segment.
+    pub fn to_pointer(&self) -> String {
+        if self.0.is_empty() {
+            return String::new();
+        }
+        self.0
+            .split('.')
+            .map(|s| format!("/{}", s.replace('~', "~0").replace('/', "~1")))
+            .collect()
+    }
 }
 
 #[derive(Clone)]
@@ -195,7 +803,7 @@ pub enum OneOrMany<T> {
 
 impl<T> OneOrMany<T> {
     /// Create an iterator over the values in the `OneOrMany`.
-    pub fn iter(&self) -> OneOrManyIterator<T> {
+    pub fn iter(&self) -> OneOrManyIterator<'_, T> {
         OneOrManyIterator { inner: self, index: 0 }
     }
 }
@@ -265,7 +873,9 @@ impl<'a> PatchIterator<'a> {
     /// Create a new `PatchIterator` for the given tags.
     pub fn new(tags: &'a [(&'a str, Vec<String>)]) -> Self {
         let indices = vec![0; tags.len()];
-        Self { tags, indices, is_done: false }
+        // A tag without values leaves no combinations to generate.
+        let is_done = tags.iter().any(|(_, values)| values.is_empty());
+        Self { tags, indices, is_done }
     }
 
     fn increment_indices(&mut self) -> bool {
@@ -1,68 +1,122 @@
-use std::collections::HashMap;
+use std::collections::{ HashMap, HashSet };
 
 use itertools::Itertools;
 use serde_json::Value;
 
 use crate::{
     data::{
+        DependsOn,
         DotPath,
         DotPathIterator,
         DotToken,
+        Generated,
         Grammar,
-        Ingredient,
+        Lang,
         Manifest,
         Modify,
+        Patch,
         PatchIterator,
+        PatchOp,
+        PatchOperation,
+        Predicate,
         Recipe,
+        RecipeKind,
         Remove,
+        SortOrder,
         Substitution,
+        Superseded,
+        Tag,
         Template,
     },
     error::Error,
+    output,
 };
 
 pub struct DataGen<'a> {
     /// The manifest to generate recipes from.
     manifest: &'a Manifest,
-    default_template: &'a Template,
+    /// The template for grammars that don't name one, if the manifest has any templates.
+    default_template: Option<&'a Template>,
 }
 
 impl<'a> DataGen<'a> {
     /// Create a new [`DataGen`] instance.
-    pub fn new(manifest: &'a Manifest) -> Result<Self, Error> {
+    pub fn new(manifest: &'a Manifest) -> Self {
         // find a template named "default" or default to the first template.
         let default_template = manifest.templates
             .iter()
             .find(|template| template.name == "default")
-            .or_else(|| manifest.templates.first())
-            .ok_or(Error::MissingDefaultTemplate)?;
+            .or_else(|| manifest.templates.first());
 
-        Ok(Self { manifest, default_template })
+        Self { manifest, default_template }
+    }
+
+    /// Find the templates a grammar applies to.
+    fn templates(&self, grammar: &Grammar) -> Result<Vec<&'a Template>, Error> {
+        match grammar.template.as_ref() {
+            // If a template is specified, use it to generate the recipe.
+            Some(templates) => {
+                templates
+                    .iter()
+                    .map(|template| self.manifest.find_template(template))
+                    .collect()
+            }
+            // If no template is specified, use the default template.
+            None => Ok(vec![self.default_template.ok_or(Error::MissingDefaultTemplate)?]),
+        }
+    }
+
+    /// Generate the collectibles of the manifest, each with the file to write it to.
+    pub fn collectibles(&self) -> Result<Vec<(String, Value)>, Error> {
+        self.manifest.collectibles
+            .iter()
+            .map(|collectible| {
+                let value = collectible.generate(&self.manifest.static_props)?;
+                Ok((collectible.output.clone(), value))
+            })
+            .collect()
+    }
+
+    /// Generate the lang entries from the manifest.
+    pub fn lang(&self) -> Result<Lang, Error> {
+        let mut lang = Lang::new();
+
+        for grammar in &self.manifest.grammars {
+            for template in self.templates(grammar)? {
+                grammar.expand_lang(template, self.manifest, &mut lang)?;
+            }
+        }
+
+        Ok(lang)
     }
 
     /// Generate the recipes from the manifest.
-    pub fn generate(&self) -> Result<Vec<Recipe>, Error> {
-        let mut recipes = Vec::<Recipe>::new();
+    pub fn generate(&self) -> Result<Vec<Generated>, Error> {
+        let mut recipes = Vec::<Generated>::new();
+
+        // Superseded recipes disabled so far, shared by all grammars so each is only disabled once.
+        let mut disabled = HashSet::new();
 
         for grammar in &self.manifest.grammars {
-            match grammar.template.as_ref() {
-                // If a template is specified, use it to generate the recipe.
-                Some(templates) => {
-                    for template in templates {
-                        let template = self.manifest.find_template(template)?;
-                        let generated = grammar.expand(template, &self.manifest.static_props)?;
-                        recipes.extend(generated);
-                    }
-                }
-                // If no template is specified, use the default template.
-                None => {
-                    let template = self.default_template;
-                    let generated = grammar.expand(template, &self.manifest.static_props)?;
-                    recipes.extend(generated);
-                }
+            for template in self.templates(grammar)? {
+                let generated = grammar.expand(template, self.manifest, &mut disabled)?;
+                recipes.extend(generated);
             }
         }
 
+        // Stable sorts keep recipes that compare equal in the order they were generated.
+        match self.manifest.sort {
+            Some(SortOrder::Output) => recipes.sort_by(|a, b| a.output.cmp(&b.output)),
+            Some(SortOrder::Tags) => {
+                recipes.sort_by(|a, b| {
+                    let a = a.tags.iter().map(|(_, value)| value);
+                    let b = b.tags.iter().map(|(_, value)| value);
+                    a.cmp(b)
+                });
+            }
+            None => {}
+        }
+
         Ok(recipes)
     }
 }
@@ -89,123 +143,359 @@ impl Manifest {
 }
 
 impl Grammar {
+    /// Resolve the values of each tag, expanding references to static properties.
+    pub fn resolve_tags(
+        &self,
+        static_props: &HashMap<String, Value>
+    ) -> Result<Vec<(&str, Vec<String>)>, Error> {
+        self.tags
+            .iter()
+            .map(|tag| Ok((tag.name.as_str(), tag.resolve(static_props)?)))
+            .collect()
+    }
+
     /// Expand the grammar into a list of recipes.
+    ///
+    /// Patches disabling superseded recipes are skipped when `disabled` already holds the same
+    /// file, path and conditions, and recorded in it otherwise.
     pub fn expand(
         &self,
         template: &Template,
-        static_props: &HashMap<String, Value>
-    ) -> Result<Vec<Recipe>, Error> {
-        let mut recipes = Vec::<Recipe>::new();
+        manifest: &Manifest,
+        disabled: &mut HashSet<(String, String, Vec<DependsOn>)>
+    ) -> Result<Vec<Generated>, Error> {
+        let mut recipes = Vec::<Generated>::new();
+        let static_props = &manifest.static_props;
+        let output = self.output
+            .as_ref()
+            .or(manifest.output.as_ref())
+            .ok_or(Error::MissingOutput)?;
 
         // Generate mappings for the tags.
-        let mut tags: Vec<(&str, Vec<String>)> = Vec::new();
-        for tag in &self.tags {
-            let mut mapped_tag_values = Vec::new();
-
-            for value in &tag.values {
-                // map anything prefixed with `@` to the corresponding static property.
-                if value.starts_with('@') {
-                    let static_value = value.trim_start_matches('@');
-                    // If the static value is empty, return an error.
-                    if static_value.is_empty() {
-                        return Err(Error::InvalidStaticProperty {
-                            prop: static_value.to_string(),
-                            value: Value::String(value.to_string()),
-                        });
-                    }
-                    // If the static value is not empty, check if it exists in the static properties.
-                    if let Some(static_value) = static_props.get(static_value) {
-                        match static_value {
-                            Value::String(s) => mapped_tag_values.push(s.to_string()),
-                            Value::Array(arr) => {
-                                for item in arr {
-                                    match item {
-                                        Value::String(s) => mapped_tag_values.push(s.to_string()),
-                                        _ => {
-                                            return Err(Error::InvalidStaticProperty {
-                                                prop: static_value.to_string(),
-                                                value: item.clone(),
-                                            });
-                                        }
+        let tags = self.resolve_tags(static_props)?;
+
+        // Create a new iterator for the patches.
+        let mut patch_iter = PatchIterator::new(&tags);
+
+        // The number of entries each removal matched across all patches.
+        let mut removed = vec![0; self.remove.len()];
+        let mut finalized = vec![0; self.finalize.remove.len()];
+
+        // The mods the recipes depend on.
+        let mut depends_on = template.depends_on.clone();
+        for dependency in &self.depends_on {
+            if !depends_on.contains(dependency) {
+                depends_on.push(dependency.clone());
+            }
+        }
+
+        for patch in &mut patch_iter {
+            // Create a new recipe, starting from the target template, and apply the patch to it.
+            let mut recipe = Value::Object(template.recipe.clone());
+
+            // Apply removals and modifications to the template.
+            apply_operations(&self.remove, &self.modify, &patch, &mut recipe, &mut removed)?;
+
+            // Apply substitutions
+            for substitution in &patch {
+                substitution.apply(&mut recipe);
+            }
+
+            // Apply removals and modifications to the substituted recipe.
+            apply_operations(
+                &self.finalize.remove,
+                &self.finalize.modify,
+                &patch,
+                &mut recipe,
+                &mut finalized
+            )?;
+
+            // Route the recipe to the file named after its tag values.
+            let mut file = output.clone();
+            for substitution in &patch {
+                file = file.replace(&substitution.placeholder(), substitution.value);
+            }
+
+            // Keep the JSON the recipe is built from, to restore the template's field order.
+            let source = recipe.clone();
+            let mut recipe = Recipe::from_value(template.kind, recipe)?;
+
+            // Apply grid operations and transformations.
+            let mut transformed = Vec::new();
+            if let Recipe::Grid(grid) = &mut recipe {
+                grid.fit_dimensions();
+                for op in &self.grid {
+                    op.apply(grid)?;
+                }
+                grid.validate()?;
+                transformed = grid.transformed(&self.transforms);
+            } else if !self.grid.is_empty() {
+                return Err(Error::UnsupportedOperation { op: "grid", kind: template.kind });
+            } else if !self.transforms.is_empty() {
+                return Err(Error::UnsupportedOperation { op: "transforms", kind: template.kind });
+            }
+
+            // Apply voxel operations.
+            if let Recipe::Voxel(voxel) = &mut recipe {
+                for op in &self.voxels {
+                    op.apply(voxel)?;
+                }
+            } else if !self.voxels.is_empty() {
+                return Err(Error::UnsupportedOperation { op: "voxels", kind: template.kind });
+            }
+
+            // Patches carry their own conditions.
+            if let Recipe::Patch(patch) = &mut recipe {
+                patch.depends_on.extend(depends_on.iter().cloned());
+            }
+
+            // Finish the recipe.
+            recipe.validate(template.kind)?;
+            let tags: Vec<_> = patch
+                .iter()
+                .map(|substitution| {
+                    (substitution.target.to_string(), substitution.value.to_string())
+                })
+                .collect();
+            for recipe in std::iter::once(recipe).chain(transformed.into_iter().map(Recipe::Grid)) {
+                let mut value = serde_json::to_value(&recipe).map_err(Error::Json)?;
+                order_like(&mut value, &source);
+                recipes.push(Generated {
+                    kind: template.kind,
+                    grammar: self.name.clone(),
+                    template: template.name.clone(),
+                    file: file.clone(),
+                    output: recipe.output_code().to_string(),
+                    tags: tags.clone(),
+                    depends_on: match recipe {
+                        Recipe::Patch(_) => Vec::new(),
+                        _ => depends_on.clone(),
+                    },
+                    recipe: value,
+                });
+            }
+
+            // Disable the recipes the template replaces, next to the recipe file.
+            for superseded in &template.supersedes {
+                let mut disable = superseded.disable(&patch);
+                disable.depends_on = depends_on.clone();
+                let key = (disable.file.clone(), disable.path.clone(), disable.depends_on.clone());
+                if !disabled.insert(key) {
+                    continue;
+                }
+                recipes.push(Generated {
+                    kind: RecipeKind::Patch,
+                    grammar: self.name.clone(),
+                    template: template.name.clone(),
+                    file: output::sibling_file(&file, "disable"),
+                    output: disable.file.clone(),
+                    tags: tags.clone(),
+                    depends_on: Vec::new(),
+                    recipe: serde_json::to_value(&disable).map_err(Error::Json)?,
+                });
+            }
+        }
+
+        // Flag predicate removals that never matched anything, as they are likely mistakes.
+        warn_unmatched(&self.remove, &removed, template);
+        warn_unmatched(&self.finalize.remove, &finalized, template);
+
+        Ok(recipes)
+    }
+}
+
+impl Tag {
+    /// Resolve the values of the tag, expanding references to static properties.
+    pub fn resolve(&self, static_props: &HashMap<String, Value>) -> Result<Vec<String>, Error> {
+        let mut mapped_tag_values = Vec::new();
+
+        for value in &self.values {
+            // map anything prefixed with `@` to the corresponding static property.
+            if value.starts_with('@') {
+                let static_value = value.trim_start_matches('@');
+                // If the static value is empty, return an error.
+                if static_value.is_empty() {
+                    return Err(Error::InvalidStaticProperty {
+                        prop: static_value.to_string(),
+                        value: Value::String(value.to_string()),
+                    });
+                }
+                // If the static value is not empty, check if it exists in the static properties.
+                if let Some(static_value) = static_props.get(static_value) {
+                    match static_value {
+                        Value::String(s) => mapped_tag_values.push(s.to_string()),
+                        Value::Array(arr) => {
+                            for item in arr {
+                                match item {
+                                    Value::String(s) => mapped_tag_values.push(s.to_string()),
+                                    _ => {
+                                        return Err(Error::InvalidStaticProperty {
+                                            prop: static_value.to_string(),
+                                            value: item.clone(),
+                                        });
                                     }
                                 }
                             }
-                            // If the static value is not a string or an array, return an error.
-                            _ => {
-                                return Err(Error::InvalidStaticProperty {
-                                    prop: static_value.to_string(),
-                                    value: static_value.clone(),
-                                });
-                            }
                         }
-                    } else {
-                        // If the static value does not exist, return an error.
-                        return Err(Error::UnknownStaticProperty(static_value.to_string()));
+                        // If the static value is not a string or an array, return an error.
+                        _ => {
+                            return Err(Error::InvalidStaticProperty {
+                                prop: static_value.to_string(),
+                                value: static_value.clone(),
+                            });
+                        }
                     }
                 } else {
-                    // Otherwise, just push the value as is.
-                    mapped_tag_values.push(value.clone());
+                    // If the static value does not exist, return an error.
+                    return Err(Error::UnknownStaticProperty(static_value.to_string()));
                 }
+            } else {
+                // Otherwise, just push the value as is.
+                mapped_tag_values.push(value.clone());
             }
-
-            tags.push((tag.name.as_str(), mapped_tag_values));
         }
 
-        // Create a new iterator for the patches.
-        let mut patch_iter = PatchIterator::new(&tags);
-
-        for patch in &mut patch_iter {
-            // Create a new recipe, starting from the target template, and apply the patch to it.
-            let mut recipe: Value = serde_json
-                ::to_value(template.recipe.clone())
-                .map_err(Error::Json)?;
+        Ok(mapped_tag_values)
+    }
+}
 
-            // Apply removals
-            for remove in &self.remove {
-                remove.apply(&mut recipe)?;
-            }
+/// Apply removals, then modifications, to a JSON value, resolving tags in each operation for the
+/// current patch. The number of entries each removal matched is added to `removed`.
+fn apply_operations(
+    remove: &[Remove],
+    modify: &[Modify],
+    patch: &Patch,
+    value: &mut Value,
+    removed: &mut [usize]
+) -> Result<(), Error> {
+    for (remove, removed) in remove.iter().zip(removed) {
+        *removed += remove.interpolate(patch).apply(value)?;
+    }
+    for modify in modify {
+        modify.interpolate(patch).apply(value)?;
+    }
+    Ok(())
+}
 
-            // Apply modifications
-            for modify in &self.modify {
-                modify.apply(&mut recipe)?;
+/// Order the fields of `value` like the fields of `source`, recursively. Fields that `source` does
+/// not have stay directly after the field they followed in `value`.
+fn order_like(value: &mut Value, source: &Value) {
+    match (value, source) {
+        (Value::Object(obj), Value::Object(source)) => {
+            for (key, value) in obj.iter_mut() {
+                if let Some(source) = source.get(key) {
+                    order_like(value, source);
+                }
             }
 
-            let mut recipe: Recipe = serde_json::from_value(recipe).map_err(Error::Json)?;
+            let positions: HashMap<&str, usize> = source
+                .keys()
+                .enumerate()
+                .map(|(i, key)| (key.as_str(), i))
+                .collect();
 
-            // Apply substitutions
-            for substitution in &patch {
-                substitution.apply(&mut recipe)?;
+            // Group each field the source does not have with the field before it.
+            type Fields = Vec<(String, Value)>;
+            let mut groups: Vec<(Option<usize>, Fields)> = Vec::new();
+            for (key, value) in std::mem::take(obj) {
+                match positions.get(key.as_str()) {
+                    Some(&position) => groups.push((Some(position), vec![(key, value)])),
+                    None =>
+                        match groups.last_mut() {
+                            Some((_, fields)) => fields.push((key, value)),
+                            None => groups.push((None, vec![(key, value)])),
+                        }
+                }
             }
 
-            // Finish the recipe.
-            recipes.push(recipe);
+            groups.sort_by_key(|(position, _)| *position);
+            obj.extend(groups.into_iter().flat_map(|(_, fields)| fields));
         }
+        (Value::Array(arr), Value::Array(source)) => {
+            for (value, source) in arr.iter_mut().zip(source) {
+                order_like(value, source);
+            }
+        }
+        _ => {}
+    }
+}
 
-        Ok(recipes)
+/// Print a warning for each predicate removal that matched no entries.
+fn warn_unmatched(remove: &[Remove], removed: &[usize], template: &Template) {
+    for (remove, &removed) in remove.iter().zip(removed) {
+        if let Remove::Where { path, predicate } = remove && removed == 0 {
+            eprintln!(
+                "Warning: removal at path `{path}` matched no entries for template `{}` (where: {})",
+                template.name,
+                serde_json::to_string(predicate).unwrap_or_default()
+            );
+        }
     }
 }
 
 impl<'a> Substitution<'a> {
-    /// Apply the substitution to a recipe.
-    pub fn apply(&self, recipe: &mut Recipe) -> Result<(), Error> {
-        // Replace all instances of "%target%" with the value.
-        let target = format!("%{}%", self.target);
-        let target = target.as_str();
-        recipe.ingredients.values_mut().for_each(|ingredient| {
-            ingredient.replace_mut(target, self.value);
-        });
-        recipe.output.replace_mut(target, self.value);
-        recipe.pattern = recipe.pattern.replace(target, self.value);
-        recipe.rest.values_mut().for_each(|value| {
-            value.replace_mut(target, self.value);
-        });
-        Ok(())
+    /// The placeholder this substitution replaces, i.e. `%target%`.
+    pub fn placeholder(&self) -> String {
+        format!("%{}%", self.target)
+    }
+
+    /// Apply the substitution to a JSON value, replacing all instances of "%target%" in its strings.
+    pub fn apply(&self, value: &mut Value) {
+        value.replace_mut(&self.placeholder(), self.value);
+    }
+}
+
+impl Superseded {
+    /// Create the patch that disables the superseded recipe, with the patch's tags resolved.
+    pub fn disable(&self, patch: &Patch) -> PatchOperation {
+        let mut file = self.file.clone();
+        for substitution in patch {
+            file = file.replace(&substitution.placeholder(), substitution.value);
+        }
+        let path = match self.index {
+            Some(index) => format!("/{index}/enabled"),
+            None => "/enabled".to_string(),
+        };
+        PatchOperation {
+            op: PatchOp::Add,
+            path,
+            file,
+            value: Some(Value::Bool(false)),
+            from_path: None,
+            depends_on: Vec::new(),
+            rest: Default::default(),
+        }
+    }
+}
+
+impl DotPath {
+    /// Create a copy of the path with the patch's tags resolved.
+    pub fn interpolate(&self, patch: &Patch) -> DotPath {
+        let mut path = self.0.clone();
+        for substitution in patch {
+            path = path.replace(&substitution.placeholder(), substitution.value);
+        }
+        DotPath(path)
     }
 }
 
 impl Modify {
+    /// Create a copy of the modification with the patch's tags resolved in its path and value.
+    pub fn interpolate(&self, patch: &Patch) -> Modify {
+        let mut value = self.value.clone();
+        for substitution in patch {
+            value.replace_mut(&substitution.placeholder(), substitution.value);
+        }
+        let condition = self.condition.as_ref().map(|condition| condition.interpolate(patch));
+        Modify { path: self.path.interpolate(patch), value, condition }
+    }
+
     /// Apply the modification to a JSON value.
     pub fn apply(&self, value: &mut Value) -> Result<(), Error> {
+        if let Some(condition) = &self.condition && !condition.matches(value) {
+            return Ok(());
+        }
+
         let mut current = value;
         let mut tokens = DotPathIterator::new(&self.path).peekable();
 
@@ -286,6 +576,7 @@ impl Modify {
                                 let sub_modify = Modify {
                                     path: sub_path,
                                     value: self.value.clone(),
+                                    condition: None,
                                 };
                                 sub_modify.apply(v)?;
                             }
@@ -298,6 +589,7 @@ impl Modify {
                                     let sub_modify = Modify {
                                         path: sub_path,
                                         value: self.value.clone(),
+                                        condition: None,
                                     };
                                     sub_modify.apply(v)?;
                                 } else {
@@ -327,34 +619,61 @@ impl Modify {
 }
 
 impl Remove {
-    /// Apply the removal to a JSON value.
-    pub fn apply(&self, value: &mut Value) -> Result<(), Error> {
+    /// Create a copy of the removal with the patch's tags resolved in its path and predicate.
+    pub fn interpolate(&self, patch: &Patch) -> Remove {
+        match self {
+            Remove::Path(path) => Remove::Path(path.interpolate(patch)),
+            Remove::Where { path, predicate } =>
+                Remove::Where {
+                    path: path.interpolate(patch),
+                    predicate: predicate.interpolate(patch),
+                },
+            Remove::When { path, condition } =>
+                Remove::When {
+                    path: path.interpolate(patch),
+                    condition: condition.interpolate(patch),
+                },
+        }
+    }
+
+    /// Apply the removal to a JSON value, returning the number of entries removed.
+    pub fn apply(&self, value: &mut Value) -> Result<usize, Error> {
+        match self {
+            Remove::Path(path) => Self::remove_path(path, value),
+            Remove::Where { path, predicate } => Self::remove_where(path, predicate, value),
+            Remove::When { path, condition } => {
+                if condition.matches(value) { Self::remove_path(path, value) } else { Ok(0) }
+            }
+        }
+    }
+
+    /// Remove the property or index at `path`.
+    fn remove_path(path: &DotPath, value: &mut Value) -> Result<usize, Error> {
         let mut current = value;
-        let mut tokens = DotPathIterator::new(&self.0).peekable();
+        let mut tokens = DotPathIterator::new(path).peekable();
 
         while let Some(token) = tokens.next() {
             match token {
                 DotToken::Property(prop) => {
                     if tokens.peek().is_none() {
                         if let Value::Object(obj) = current {
-                            obj.remove(prop);
-                            return Ok(());
+                            return Ok(usize::from(obj.shift_remove(prop).is_some()));
                         } else {
                             return Err(Error::ExpectedObjectToRemoveProperty {
-                                path: self.0.to_string(),
+                                path: path.to_string(),
                                 prop: prop.to_string(),
                             });
                         }
                     } else if let Value::Object(obj) = current {
                         current = obj.get_mut(prop).ok_or_else(|| {
                             Error::UnknownPropertyInObjectPath {
-                                path: self.0.to_string(),
+                                path: path.to_string(),
                                 prop: prop.to_string(),
                             }
                         })?;
                     } else {
                         return Err(Error::ExpectedObjectToRemoveProperty {
-                            path: self.0.to_string(),
+                            path: path.to_string(),
                             prop: prop.to_string(),
                         });
                     }
@@ -366,14 +685,14 @@ impl Remove {
                                 return Err(Error::IndexOutOfBounds {
                                     index,
                                     len: arr.len(),
-                                    path: self.0.to_string(),
+                                    path: path.to_string(),
                                 });
                             }
                             arr.remove(index);
-                            return Ok(());
+                            return Ok(1);
                         } else {
                             return Err(Error::ExpectedArrayToRemoveIndex {
-                                path: self.0.to_string(),
+                                path: path.to_string(),
                                 index,
                             });
                         }
@@ -382,50 +701,208 @@ impl Remove {
                             return Err(Error::IndexOutOfBounds {
                                 index,
                                 len: arr.len(),
-                                path: self.0.to_string(),
+                                path: path.to_string(),
                             });
                         }
                         current = &mut arr[index];
                     } else {
                         return Err(Error::ExpectedArrayToRemoveIndex {
-                            path: self.0.to_string(),
+                            path: path.to_string(),
                             index,
                         });
                     }
                 }
                 DotToken::Wildcard => {
+                    let sub_path = DotPath(tokens.clone().join("."));
+                    let mut removed = 0;
                     match current {
                         Value::Object(obj) => {
                             for v in obj.values_mut() {
-                                let sub_path = DotPath(tokens.clone().join("."));
-                                let sub_remove = Remove(sub_path);
-                                sub_remove.apply(v)?;
+                                removed += Self::remove_path(&sub_path, v)?;
                             }
                         }
                         Value::Array(arr) => {
                             for v in arr.iter_mut() {
-                                let sub_path = DotPath(tokens.clone().join("."));
-                                let sub_remove = Remove(sub_path);
-                                sub_remove.apply(v)?;
+                                removed += Self::remove_path(&sub_path, v)?;
                             }
                         }
                         _ => {
                             return Err(Error::ExpectedWildcardToRemoveProperty {
-                                path: self.0.to_string(),
+                                path: path.to_string(),
                                 value: current.clone(),
                             });
                         }
                     }
 
-                    return Ok(());
+                    return Ok(removed);
                 }
             }
         }
 
-        Ok(())
+        Ok(0)
+    }
+
+    /// Remove every entry of the array or object at `path` that matches `predicate`.
+    fn remove_where(
+        path: &DotPath,
+        predicate: &Predicate,
+        value: &mut Value
+    ) -> Result<usize, Error> {
+        let mut current = value;
+        let mut tokens = DotPathIterator::new(path);
+
+        // An empty path filters the root value itself.
+        if !path.0.is_empty() {
+            while let Some(token) = tokens.next() {
+                match token {
+                    DotToken::Property(prop) => {
+                        if let Value::Object(obj) = current {
+                            current = obj.get_mut(prop).ok_or_else(|| {
+                                Error::UnknownPropertyInObjectPath {
+                                    path: path.to_string(),
+                                    prop: prop.to_string(),
+                                }
+                            })?;
+                        } else {
+                            return Err(Error::ExpectedObjectToRemoveProperty {
+                                path: path.to_string(),
+                                prop: prop.to_string(),
+                            });
+                        }
+                    }
+                    DotToken::Index(index) => {
+                        if let Value::Array(arr) = current {
+                            let len = arr.len();
+                            current = arr.get_mut(index).ok_or_else(|| {
+                                Error::IndexOutOfBounds {
+                                    index,
+                                    len,
+                                    path: path.to_string(),
+                                }
+                            })?;
+                        } else {
+                            return Err(Error::ExpectedArrayToRemoveIndex {
+                                path: path.to_string(),
+                                index,
+                            });
+                        }
+                    }
+                    DotToken::Wildcard => {
+                        let sub_path = DotPath(tokens.clone().join("."));
+                        let mut removed = 0;
+                        match current {
+                            Value::Object(obj) => {
+                                for v in obj.values_mut() {
+                                    removed += Self::remove_where(&sub_path, predicate, v)?;
+                                }
+                            }
+                            Value::Array(arr) => {
+                                for v in arr.iter_mut() {
+                                    removed += Self::remove_where(&sub_path, predicate, v)?;
+                                }
+                            }
+                            _ => {
+                                return Err(Error::ExpectedWildcardToRemoveProperty {
+                                    path: path.to_string(),
+                                    value: current.clone(),
+                                });
+                            }
+                        }
+
+                        return Ok(removed);
+                    }
+                }
+            }
+        }
+
+        match current {
+            Value::Object(obj) => {
+                let len = obj.len();
+                obj.retain(|_, entry| !predicate.matches(entry));
+                Ok(len - obj.len())
+            }
+            Value::Array(arr) => {
+                let len = arr.len();
+                arr.retain(|entry| !predicate.matches(entry));
+                Ok(len - arr.len())
+            }
+            _ =>
+                Err(Error::ExpectedCollectionToRemoveWhere {
+                    path: path.to_string(),
+                    value: current.clone(),
+                }),
+        }
+    }
+}
+
+impl Predicate {
+    /// Create a copy of the predicate with the patch's tags resolved in its values.
+    pub fn interpolate(&self, patch: &Patch) -> Predicate {
+        let mut predicate = self.clone();
+        for value in predicate.0.values_mut() {
+            for substitution in patch {
+                value.replace_mut(&substitution.placeholder(), substitution.value);
+            }
+        }
+        predicate
+    }
+
+    /// Check whether every condition of the predicate holds for `entry`.
+    pub fn matches(&self, entry: &Value) -> bool {
+        self.0.iter().all(|(path, expected)| {
+            let pointer = DotPath(path.clone()).to_pointer();
+            match (entry.pointer(&pointer), expected) {
+                (Some(Value::String(actual)), Value::String(pattern)) =>
+                    glob_match(pattern, actual),
+                (Some(actual), expected) => actual == expected,
+                (None, _) => false,
+            }
+        })
     }
 }
 
+/// Match `text` against a glob `pattern`, where `*` matches any run of characters and `?`
+/// matches a single character.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // The position of the last `*` in the pattern, and the text position it was tried at.
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                t += 1;
+            }
+            Some(&c) if c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => {
+                // Let the last `*` swallow one more character, or fail.
+                match backtrack {
+                    Some((star, star_t)) => {
+                        p = star + 1;
+                        t = star_t + 1;
+                        backtrack = Some((star, star_t + 1));
+                    }
+                    None => {
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
 pub trait ReplaceMut {
     fn replace_mut(&mut self, target: &str, value: &str);
 }
@@ -451,19 +928,183 @@ impl ReplaceMut for Value {
     }
 }
 
-impl ReplaceMut for Ingredient {
-    /// Replace all instances of `target` with `value` in the ingredient.
-    fn replace_mut(&mut self, target: &str, value: &str) {
-        self.code = self.code.replace(target, value);
-        self.item_type = self.item_type.replace(target, value);
-        if let Some(name) = &mut self.name {
-            *name = name.replace(target, value);
-        }
-        for variant in &mut self.skip_variants {
-            *variant = variant.replace(target, value);
-        }
-        for v in self.rest.values_mut() {
-            v.replace_mut(target, value);
-        }
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn disable_superseded_once() {
+        let manifest: Manifest = serde_json
+            ::from_value(
+                json!({
+                "output": "shovel-%metal%.json",
+                "templates": [{
+                    "name": "default",
+                    "kind": "generic",
+                    "code": "shovel-%metal%",
+                    "supersedes": [{ "file": "game:recipes/grid/tool/shovel.json", "index": 2 }],
+                }],
+                "grammars": [
+                    { "tags": [{ "name": "metal", "values": ["copper", "tinbronze"] }] },
+                    { "output": "extra.json", "tags": [{ "name": "metal", "values": ["gold"] }] },
+                    {
+                        "output": "mod-%metal%.json",
+                        "dependsOn": [{ "modid": "extramaterials" }],
+                        "tags": [{ "name": "metal", "values": ["zinc"] }],
+                    },
+                ],
+            })
+            )
+            .unwrap();
+        let recipes = DataGen::new(&manifest).generate().unwrap();
+        let patches: Vec<_> = recipes
+            .iter()
+            .filter(|recipe| recipe.kind == RecipeKind::Patch)
+            .collect();
+
+        assert_eq!(patches.len(), 2);
+        assert_eq!(patches[0].file, "shovel-copper-disable.json");
+        assert_eq!(
+            patches[0].recipe,
+            json!({
+                "op": "add",
+                "path": "/2/enabled",
+                "file": "game:recipes/grid/tool/shovel.json",
+                "value": false,
+            })
+        );
+        assert_eq!(patches[1].file, "mod-zinc-disable.json");
+        assert_eq!(patches[1].recipe["dependsOn"], json!([{ "modid": "extramaterials" }]));
+    }
+
+    #[test]
+    fn glob_matching() {
+        assert!(glob_match("hammer-*", "hammer-copper"));
+        assert!(glob_match("*-gold", "shovel-fine-gold"));
+        assert!(glob_match("a?c*", "abcdef"));
+        assert!(!glob_match("hammer-*", "shovelhead-copper"));
+        assert!(!glob_match("a?c", "ac"));
+    }
+
+    #[test]
+    fn remove_where_counts_matches() {
+        let mut recipe = json!({
+            "ingredients": {
+                "H": { "code": "hammer-*" },
+                "S": { "code": "stick" },
+            }
+        });
+        let remove: Remove = serde_json::from_value(json!({
+            "path": "ingredients",
+            "where": { "code": "hammer-*" }
+        })).unwrap();
+
+        assert_eq!(remove.apply(&mut recipe).unwrap(), 1);
+        assert_eq!(recipe, json!({ "ingredients": { "S": { "code": "stick" } } }));
+        assert_eq!(remove.apply(&mut recipe).unwrap(), 0);
+    }
+
+    #[test]
+    fn resolve_tags_in_operation_paths() {
+        let manifest: Manifest = serde_json
+            ::from_value(
+                json!({
+                "output": "tools.json",
+                "templates": [{
+                    "name": "default",
+                    "ingredientPattern": "ST",
+                    "ingredients": {
+                        "S": { "type": "item", "code": "stick", "quantity": 2 },
+                        "T": { "type": "item", "code": "head", "quantity": 2 },
+                    },
+                    "output": { "type": "item", "code": "tool-%slot%" },
+                }],
+                "grammars": [{
+                    "tags": [{ "name": "slot", "values": ["S", "T"] }],
+                    "remove": ["ingredients.%slot%.quantity"],
+                    "modify": [{ "path": "ingredients.%slot%.code", "value": "iron-%slot%" }],
+                }],
+            })
+            )
+            .unwrap();
+        let recipes = DataGen::new(&manifest).generate().unwrap();
+
+        // Each combination changes only the ingredient its own tag value names.
+        assert_eq!(recipes.len(), 2);
+        assert_eq!(
+            recipes[0].recipe["ingredients"],
+            json!({
+                "S": { "type": "item", "code": "iron-S" },
+                "T": { "type": "item", "code": "head", "quantity": 2 },
+            })
+        );
+        assert_eq!(
+            recipes[1].recipe["ingredients"],
+            json!({
+                "S": { "type": "item", "code": "stick", "quantity": 2 },
+                "T": { "type": "item", "code": "iron-T" },
+            })
+        );
+    }
+
+    #[test]
+    fn finalize_sees_substituted_values() {
+        let manifest: Manifest = serde_json
+            ::from_value(
+                json!({
+                "output": "shovels.json",
+                "templates": [{
+                    "name": "default",
+                    "ingredientPattern": "M",
+                    "ingredients": { "M": { "type": "item", "code": "ingot-%metal%" } },
+                    "output": {
+                        "type": "item",
+                        "code": "shovel-%metal%",
+                        "attributes": { "durability": 100 },
+                    },
+                }],
+                "grammars": [
+                    {
+                        "tags": [{ "name": "metal", "values": ["copper", "gold"] }],
+                        "finalize": {
+                            "remove": [
+                                { "path": "output.attributes", "if": { "output.code": "*-gold" } },
+                            ],
+                        },
+                    },
+                    {
+                        "tags": [{ "name": "metal", "values": ["gold"] }],
+                        "remove": [
+                            { "path": "output.attributes", "if": { "output.code": "*-gold" } },
+                        ],
+                    },
+                ],
+            })
+            )
+            .unwrap();
+        let recipes = DataGen::new(&manifest).generate().unwrap();
+        let attributes: Vec<_> = recipes
+            .iter()
+            .map(|recipe| {
+                (recipe.output.as_str(), recipe.recipe["output"].get("attributes").is_some())
+            })
+            .collect();
+
+        // Before substitution, the code is still `shovel-%metal%` and matches nothing.
+        assert_eq!(attributes, [
+            ("shovel-copper", true),
+            ("shovel-gold", false),
+            ("shovel-gold", true),
+        ]);
+    }
+
+    #[test]
+    fn order_fields_like_source() {
+        let source = json!({ "b": 1, "a": { "y": 1, "x": 2 }, "c": 3 });
+        let mut value = json!({ "a": { "x": 2, "y": 1 }, "c": 3, "new": 4, "b": 1 });
+        order_like(&mut value, &source);
+        assert_eq!(value.to_string(), r#"{"b":1,"a":{"y":1,"x":2},"c":3,"new":4}"#);
     }
 }
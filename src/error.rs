@@ -1,10 +1,18 @@
+use std::path::PathBuf;
+
 use serde_json::Value;
 
+use crate::data::RecipeKind;
+
 #[derive(Debug)]
 pub enum Error {
     Io(std::io::Error),
     Json(serde_json::Error),
+    Json5(json5::Error),
+    Zip(zip::result::ZipError),
     MissingDefaultTemplate,
+    MissingOutput,
+    NotAManifest(PathBuf),
     UnknownTemplate(String),
     UnknownStaticProperty(String),
     InvalidStaticProperty {
@@ -44,6 +52,65 @@ pub enum Error {
         path: String,
         value: Value,
     },
+    ExpectedCollectionToRemoveWhere {
+        path: String,
+        value: Value,
+    },
+    UnknownIngredientKey(char),
+    DuplicateIngredientKey(char),
+    ReservedIngredientKey(char),
+    CellOutOfBounds {
+        row: usize,
+        col: usize,
+        width: usize,
+        height: usize,
+    },
+    PatternSizeMismatch {
+        output: String,
+        pattern: String,
+        width: u8,
+        height: u8,
+    },
+    MissingIngredient {
+        output: String,
+        pattern: String,
+        key: char,
+    },
+    UnusedIngredient {
+        output: String,
+        pattern: String,
+        key: char,
+    },
+    UnsupportedOperation {
+        op: &'static str,
+        kind: RecipeKind,
+    },
+    InvalidRecipe {
+        output: String,
+        reason: String,
+    },
+    MissingDomain,
+    MissingDependsOnDomain,
+    UnknownFilter(String),
+    ConflictingLangEntry {
+        locale: String,
+        key: String,
+        first: String,
+        second: String,
+    },
+    DuplicatePackageEntry(String),
+    DuplicateAssetFile(PathBuf),
+    UnknownCodes(usize),
+    MissingAssets(usize),
+    UnknownAssetReference(String),
+    EmptyVariantGroup(String),
+    UnloadedCollectible(PathBuf),
+    VoxelOutOfBounds {
+        output: String,
+        layer: usize,
+        x: isize,
+        z: isize,
+    },
 }
 
 impl std::fmt::Display for Error {
@@ -51,7 +118,17 @@ impl std::fmt::Display for Error {
         match self {
             Error::Io(err) => err.fmt(f),
             Error::Json(err) => err.fmt(f),
+            Error::Json5(err) => err.fmt(f),
+            Error::Zip(err) => err.fmt(f),
             Error::MissingDefaultTemplate => write!(f, "Missing default template"),
+            Error::MissingOutput =>
+                write!(f, "Missing output file, set in the manifest or on the grammar"),
+            Error::NotAManifest(path) =>
+                write!(
+                    f,
+                    "{} is not a manifest, as it declares no templates, grammars or collectibles",
+                    path.display()
+                ),
             Error::UnknownTemplate(alias) => write!(f, "Unknown template alias: {alias}"),
             Error::UnknownStaticProperty(prop) => write!(f, "Missing static property: {prop}"),
             Error::InvalidStaticProperty { prop, value } =>
@@ -78,6 +155,83 @@ impl std::fmt::Display for Error {
                 write!(f, "Expected wildcard to set property at path: {path}. Value: {value}"),
             Error::ExpectedWildcardToRemoveProperty { path, value } =>
                 write!(f, "Expected wildcard to remove property at path: {path}. Value: {value}"),
+            Error::ExpectedCollectionToRemoveWhere { path, value } =>
+                write!(
+                    f,
+                    "Expected array or object to remove matching entries at path: {path}. Value: {value}"
+                ),
+            Error::UnknownIngredientKey(key) => write!(f, "Unknown ingredient key: {key}"),
+            Error::DuplicateIngredientKey(key) =>
+                write!(f, "Ingredient key is already in use: {key}"),
+            Error::ReservedIngredientKey(key) =>
+                write!(f, "Ingredient key is reserved for empty cells: {key}"),
+            Error::CellOutOfBounds { row, col, width, height } =>
+                write!(
+                    f,
+                    "Cell is outside of the pattern. Row: {row}, Column: {col}, Width: {width}, Height: {height}"
+                ),
+            Error::PatternSizeMismatch { output, pattern, width, height } =>
+                write!(
+                    f,
+                    "Pattern {pattern} of recipe {output} does not have {height} rows of {width} cells"
+                ),
+            Error::MissingIngredient { output, pattern, key } =>
+                write!(
+                    f,
+                    "Pattern {pattern} of recipe {output} uses key {key}, which has no ingredient"
+                ),
+            Error::UnusedIngredient { output, pattern, key } =>
+                write!(
+                    f,
+                    "Ingredient {key} of recipe {output} is not used by pattern {pattern}"
+                ),
+            Error::UnsupportedOperation { op, kind } =>
+                write!(f, "Operation {op} is not supported by {kind} recipes"),
+            Error::InvalidRecipe { output, reason } =>
+                write!(f, "Invalid recipe {output}: {reason}"),
+            Error::DuplicateAssetFile(path) =>
+                write!(
+                    f,
+                    "More than one asset would be written to {}. Generic assets need an output with tag placeholders to get a file each",
+                    path.display()
+                ),
+            Error::DuplicatePackageEntry(name) =>
+                write!(f, "More than one file would be packaged as: {name}"),
+            Error::UnknownCodes(count) =>
+                write!(f, "{count} item or block codes match nothing in the asset directories"),
+            Error::MissingAssets(count) =>
+                write!(f, "{count} texture or shape paths match no file in the asset directories"),
+            Error::EmptyVariantGroup(reference) =>
+                write!(f, "Variant group {reference} has no states to use as tag values"),
+            Error::UnloadedCollectible(path) =>
+                write!(
+                    f,
+                    "Collectible {} was not loaded, as the manifest wasn't read from a file",
+                    path.display()
+                ),
+            Error::UnknownAssetReference(reference) =>
+                write!(
+                    f,
+                    "Unknown variant group {reference}. Is its asset folder given with --assets?"
+                ),
+            Error::UnknownFilter(filter) => write!(f, "Unknown placeholder filter: {filter}"),
+            Error::ConflictingLangEntry { locale, key, first, second } =>
+                write!(
+                    f,
+                    "Lang entry {key} for locale {locale} is generated as both {first:?} and {second:?}"
+                ),
+            Error::MissingDomain =>
+                write!(f, "The mod layout requires a domain, set in the manifest or with --domain"),
+            Error::MissingDependsOnDomain =>
+                write!(
+                    f,
+                    "Recipes with dependsOn need a domain to build the patch that enables them, set in the manifest or with --domain"
+                ),
+            Error::VoxelOutOfBounds { output, layer, x, z } =>
+                write!(
+                    f,
+                    "Shifting layer {layer} of recipe {output} by x: {x}, z: {z} moves voxels off the layer"
+                ),
         }
     }
 }
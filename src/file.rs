@@ -1,6 +1,16 @@
-use std::{ collections::VecDeque, path::PathBuf };
+use std::{ collections::{ HashSet, VecDeque }, path::{ Path, PathBuf } };
 
-use crate::{ data::Manifest, error::Error };
+use itertools::Itertools;
+use serde_json::Value;
+
+use crate::{
+    data::{ CollectibleSource, Manifest, Translations },
+    error::Error,
+    package::collect_files,
+};
+
+/// The fields a file must declare at least one of to be read as a manifest.
+const MANIFEST_FIELDS: [&str; 3] = ["templates", "grammars", "collectibles"];
 
 /// A trait that provides an iterator over the contents of a list of input files and directories.
 pub trait ManifestIter<'a> {
@@ -9,16 +19,28 @@ pub trait ManifestIter<'a> {
 
 impl<'a> ManifestIter<'a> for &'a [PathBuf] {
     fn iter_manifests(self) -> ManifestIterator<'a> {
-        ManifestIterator { current: 0, paths: self, stack: Default::default() }
+        ManifestIterator {
+            current: 0,
+            paths: self,
+            stack: Default::default(),
+            root: Default::default(),
+            referenced: Default::default(),
+        }
     }
 }
 
 /// An iterator that traverses a list of input files and directories
 /// and yields the contents of each file as an [`Manifest`] object.
+///
+/// Files within a directory that a manifest in it references, such as translation tables or
+/// collectibles to update, are skipped, as they aren't manifests themselves.
 pub struct ManifestIterator<'a> {
     current: usize,
     paths: &'a [PathBuf],
     stack: VecDeque<PathBuf>,
+    /// The directory given as input that the paths on the stack are in.
+    root: PathBuf,
+    referenced: HashSet<PathBuf>,
 }
 
 impl Iterator for ManifestIterator<'_> {
@@ -32,40 +54,119 @@ impl Iterator for ManifestIterator<'_> {
             }
 
             // Grab the next path to check
-            let path = if self.stack.is_empty() {
-                let path = &self.paths[self.current];
+            let (path, listed) = if self.stack.is_empty() {
+                let path = self.paths[self.current].clone();
                 self.current += 1;
-                path
+                (path, true)
             } else {
-                self.stack.front()?
+                (self.stack.pop_front()?, false)
             };
 
             // Check if the path is a file
             if path.is_file() {
-                let content = Manifest::try_from(path);
-                self.stack.pop_front();
+                // Files given on the command line are always read.
+                if !listed && self.referenced.contains(&canonical(&path)) {
+                    continue;
+                }
 
-                // Return the content of the file
-                return Some(content);
+                // Return the content of the file, named relative to the directory it was found in.
+                let manifest = Manifest::try_from(&path).map(|mut manifest| {
+                    if !listed && let Ok(name) = path.strip_prefix(&self.root) {
+                        manifest.name = name
+                            .iter()
+                            .map(|part| part.to_string_lossy())
+                            .join("/");
+                    }
+                    manifest
+                });
+                return Some(manifest);
             }
 
             // Check if the path is a directory
             if path.is_dir() {
-                let entries = std::fs::read_dir(path).ok()?;
-                self.stack.pop_front();
-
-                for entry in entries {
-                    let entry = entry.ok()?;
-                    self.stack.push_back(entry.path());
+                // Find the files the manifests in the directory reference before reading any.
+                if listed {
+                    self.root = path.clone();
+                    match referenced_files(&path) {
+                        Ok(referenced) => self.referenced.extend(referenced),
+                        Err(err) => {
+                            return Some(Err(err));
+                        }
+                    }
                 }
 
+                let entries = std::fs::read_dir(&path).ok()?;
+
+                // Sort the entries, as the order `read_dir` yields them in is platform-specific.
+                let mut entries = entries
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .collect::<Result<Vec<_>, _>>()
+                    .ok()?;
+                entries.sort();
+                self.stack.extend(entries);
+
                 // Continue to the next iteration to process the directory
                 continue;
             }
+        }
+    }
+}
 
-            self.stack.pop_front();
+impl Manifest {
+    /// Parse a manifest, which must declare at least one of [`MANIFEST_FIELDS`] so that other JSON
+    /// files aren't mistaken for an empty manifest.
+    fn parse(path: &Path, input: &str) -> Result<Self, Error> {
+        let input: Value = serde_json::from_str(input).map_err(Error::Json)?;
+        if !MANIFEST_FIELDS.iter().any(|field| input.get(field).is_some()) {
+            return Err(Error::NotAManifest(path.to_path_buf()));
         }
+        let mut manifest: Manifest = serde_json::from_value(input).map_err(Error::Json)?;
+        manifest.path = path.to_path_buf();
+        manifest.name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        Ok(manifest)
+    }
+
+    /// The files the manifest loads, relative to its own directory.
+    fn references(&self) -> impl Iterator<Item = &Path> {
+        let translations = self.translations.values().filter_map(|translations| {
+            match translations {
+                Translations::File(file) => Some(file.as_path()),
+                Translations::Table(_) => None,
+            }
+        });
+        let collectibles = self.collectibles.iter().filter_map(|collectible| {
+            match &collectible.template {
+                CollectibleSource::File(file) => Some(file.as_path()),
+                CollectibleSource::Inline(_) => None,
+            }
+        });
+        translations.chain(collectibles)
+    }
+}
+
+/// The files that manifests under `dir` reference. Files that can't be read as manifests are left
+/// for the iterator to report.
+fn referenced_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    collect_files(dir, dir, &mut files)?;
+
+    let mut referenced = Vec::new();
+    for (_, file) in files {
+        let Ok(input) = std::fs::read_to_string(&file) else {
+            continue;
+        };
+        let Ok(manifest) = Manifest::parse(&file, &input) else {
+            continue;
+        };
+        let dir = file.parent().unwrap_or(Path::new(""));
+        referenced.extend(manifest.references().map(|reference| canonical(&dir.join(reference))));
     }
+    Ok(referenced)
+}
+
+/// The canonical form of a path, so that references spelled differently compare equal.
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
 }
 
 impl TryFrom<&PathBuf> for Manifest {
@@ -73,7 +174,71 @@ impl TryFrom<&PathBuf> for Manifest {
 
     fn try_from(path: &PathBuf) -> Result<Self, Self::Error> {
         let input = std::fs::read_to_string(path).map_err(Error::Io)?;
-        let input: Manifest = serde_json::from_str(&input).map_err(Error::Json)?;
+        let mut input = Manifest::parse(path, &input)?;
+
+        // Load translation tables kept in their own files.
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for translations in input.translations.values_mut() {
+            if let Translations::File(file) = translations {
+                let table = std::fs::read_to_string(dir.join(&file)).map_err(Error::Io)?;
+                *translations = Translations::Table(
+                    serde_json::from_str(&table).map_err(Error::Json)?
+                );
+            }
+        }
+
+        // Load collectibles to update from their files.
+        for collectible in &mut input.collectibles {
+            if let CollectibleSource::File(file) = &collectible.template {
+                let text = std::fs::read_to_string(dir.join(file)).map_err(Error::Io)?;
+                collectible.template = CollectibleSource::Inline(
+                    json5::from_str(text.trim_start_matches('\u{feff}')).map_err(Error::Json5)?
+                );
+            }
+        }
+
         Ok(input)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skip_referenced_files() {
+        let dir = std::env::temp_dir().join(format!("vsgen-references-{}", std::process::id()));
+        let itemtypes = dir.join("itemtypes");
+        std::fs::create_dir_all(&itemtypes).unwrap();
+        std::fs
+            ::write(
+                dir.join("manifest.json"),
+                r#"{ "collectibles": [
+                    { "template": "itemtypes/shovel.json5", "output": "shovel.json", "variantgroups": [] },
+                    { "template": "./itemtypes/axe.json", "output": "axe.json", "variantgroups": [] }
+                ], "translations": { "fr": "fr.json" } }"#
+            )
+            .unwrap();
+        std::fs::write(itemtypes.join("shovel.json5"), "{ code: 'shovel' }").unwrap();
+        std::fs::write(itemtypes.join("axe.json"), r#"{ "code": "axe" }"#).unwrap();
+        std::fs::write(itemtypes.join("pick.json"), r#"{ "code": "pick" }"#).unwrap();
+        std::fs::write(dir.join("fr.json"), r#"{ "copper": "cuivre" }"#).unwrap();
+        std::fs::create_dir_all(dir.join("tools")).unwrap();
+        std::fs::write(dir.join("tools").join("shovel.json"), r#"{ "templates": [] }"#).unwrap();
+
+        let paths = [dir.clone()];
+        let manifests: Vec<_> = paths.iter_manifests().collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // The unreferenced itemtype is reported rather than read as an empty manifest.
+        assert_eq!(manifests.len(), 3);
+        assert_eq!(manifests[0].as_ref().unwrap().collectibles.len(), 2);
+        assert!(
+            matches!(&manifests[1], Err(Error::NotAManifest(path)) if path.ends_with("pick.json"))
+        );
+
+        // Manifests are named relative to the input directory, whatever its path.
+        assert_eq!(manifests[0].as_ref().unwrap().name, "manifest.json");
+        assert_eq!(manifests[2].as_ref().unwrap().name, "tools/shovel.json");
+    }
+}
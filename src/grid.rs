@@ -0,0 +1,304 @@
+use serde_json::Value;
+
+use crate::{ data::{ GridOp, GridRecipe, PatternTransform }, error::Error };
+
+/// The character used for an empty cell in an ingredient pattern.
+pub const EMPTY: char = '_';
+
+impl GridRecipe {
+    /// Split the ingredient pattern into rows of cells.
+    pub fn rows(&self) -> Vec<Vec<char>> {
+        self.pattern
+            .split(',')
+            .map(|row| row.chars().collect())
+            .collect()
+    }
+
+    /// Replace the ingredient pattern with the given rows, and update the width and height to
+    /// match.
+    pub fn set_rows(&mut self, rows: &[Vec<char>]) {
+        self.pattern = rows
+            .iter()
+            .map(|row| row.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join(",");
+        self.height = rows.len() as u8;
+        self.width = rows.iter().map(Vec::len).max().unwrap_or_default() as u8;
+    }
+
+    /// Check whether an ingredient key appears anywhere in the pattern.
+    pub fn uses_key(&self, key: char) -> bool {
+        self.pattern.contains(key)
+    }
+
+    /// Compute the width and height from the pattern if either was omitted.
+    pub fn fit_dimensions(&mut self) {
+        if self.width == 0 || self.height == 0 {
+            let rows = self.rows();
+            self.set_rows(&rows);
+        }
+    }
+
+    /// Check that the pattern has `height` rows of `width` cells, that every key in the pattern has
+    /// an ingredient, and that every ingredient is used by the pattern. No ingredient may use the
+    /// key of empty cells.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.ingredients.contains_key(&EMPTY) {
+            return Err(Error::ReservedIngredientKey(EMPTY));
+        }
+
+        let rows = self.rows();
+        if
+            rows.len() != (self.height as usize) ||
+            rows.iter().any(|row| row.len() != (self.width as usize))
+        {
+            return Err(Error::PatternSizeMismatch {
+                output: self.output.code.clone(),
+                pattern: self.pattern.clone(),
+                width: self.width,
+                height: self.height,
+            });
+        }
+
+        if
+            let Some(&key) = rows
+                .iter()
+                .flatten()
+                .find(|&&key| key != EMPTY && !self.ingredients.contains_key(&key))
+        {
+            return Err(Error::MissingIngredient {
+                output: self.output.code.clone(),
+                pattern: self.pattern.clone(),
+                key,
+            });
+        }
+
+        if let Some(&key) = self.ingredients.keys().find(|&&key| !self.uses_key(key)) {
+            return Err(Error::UnusedIngredient {
+                output: self.output.code.clone(),
+                pattern: self.pattern.clone(),
+                key,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl PatternTransform {
+    /// Apply the transformation to the rows of a pattern.
+    pub fn apply(&self, rows: &[Vec<char>]) -> Vec<Vec<char>> {
+        let height = rows.len();
+        let width = rows.iter().map(Vec::len).max().unwrap_or_default();
+        let cell = |row: usize, col: usize| rows[row].get(col).copied().unwrap_or(EMPTY);
+
+        match self {
+            PatternTransform::Mirror =>
+                (0..height).map(|row| (0..width).rev().map(|col| cell(row, col)).collect()).collect(),
+            PatternTransform::Flip => rows.iter().rev().cloned().collect(),
+            PatternTransform::Rotate90 =>
+                (0..width).map(|col| (0..height).rev().map(|row| cell(row, col)).collect()).collect(),
+            PatternTransform::Rotate180 =>
+                (0..height)
+                    .rev()
+                    .map(|row| (0..width).rev().map(|col| cell(row, col)).collect())
+                    .collect(),
+            PatternTransform::Rotate270 =>
+                (0..width)
+                    .rev()
+                    .map(|col| (0..height).map(|row| cell(row, col)).collect())
+                    .collect(),
+        }
+    }
+}
+
+impl GridRecipe {
+    /// Create a copy of the recipe with each transformation applied to its pattern, skipping any
+    /// copy whose pattern is identical to the original or an earlier copy.
+    pub fn transformed(&self, transforms: &[PatternTransform]) -> Vec<GridRecipe> {
+        let rows = self.rows();
+        let mut patterns = vec![self.pattern.clone()];
+        let mut recipes = Vec::new();
+
+        for transform in transforms {
+            let mut recipe = self.clone();
+            recipe.set_rows(&transform.apply(&rows));
+            if !patterns.contains(&recipe.pattern) {
+                patterns.push(recipe.pattern.clone());
+                recipes.push(recipe);
+            }
+        }
+
+        recipes
+    }
+}
+
+impl GridOp {
+    /// Apply the grid operation to a recipe.
+    pub fn apply(&self, recipe: &mut GridRecipe) -> Result<(), Error> {
+        match *self {
+            GridOp::RenameKey { from, to } => {
+                if from == to {
+                    return Ok(());
+                }
+                if to == EMPTY {
+                    return Err(Error::ReservedIngredientKey(to));
+                }
+                if recipe.ingredients.contains_key(&to) {
+                    return Err(Error::DuplicateIngredientKey(to));
+                }
+                // Keep the renamed ingredient in the same position.
+                let (index, _, ingredient) = recipe.ingredients
+                    .shift_remove_full(&from)
+                    .ok_or(Error::UnknownIngredientKey(from))?;
+                recipe.ingredients.shift_insert(index, to, ingredient);
+                recipe.pattern = recipe.pattern.replace(from, &to.to_string());
+
+                // Keep attribute copying pointed at the renamed ingredient.
+                if
+                    let Some(Value::String(key)) = recipe.rest.get_mut("copyAttributesFrom") &&
+                    *key == from.to_string()
+                {
+                    *key = to.to_string();
+                }
+            }
+            GridOp::SwapKeys { a, b } => {
+                for key in [a, b] {
+                    if !recipe.ingredients.contains_key(&key) {
+                        return Err(Error::UnknownIngredientKey(key));
+                    }
+                }
+                recipe.pattern = recipe.pattern
+                    .chars()
+                    .map(|c| if c == a { b } else if c == b { a } else { c })
+                    .collect();
+            }
+            GridOp::SetCell { row, col, key } => {
+                if key != EMPTY && !recipe.ingredients.contains_key(&key) {
+                    return Err(Error::UnknownIngredientKey(key));
+                }
+                let mut rows = recipe.rows();
+                let cell = rows
+                    .get_mut(row)
+                    .and_then(|cells| cells.get_mut(col))
+                    .ok_or(Error::CellOutOfBounds {
+                        row,
+                        col,
+                        width: recipe.width as usize,
+                        height: recipe.height as usize,
+                    })?;
+                let replaced = std::mem::replace(cell, key);
+                recipe.set_rows(&rows);
+
+                // Drop the ingredient that was overwritten if it is no longer used.
+                if replaced != EMPTY && !recipe.uses_key(replaced) {
+                    recipe.ingredients.shift_remove(&replaced);
+                }
+            }
+            GridOp::PadPattern { top, bottom, left, right } => {
+                let width = (recipe.width as usize) + left + right;
+                let mut rows = vec![vec![EMPTY; width]; top];
+                for cells in recipe.rows() {
+                    let mut row = vec![EMPTY; left];
+                    row.extend(cells);
+                    row.resize(width, EMPTY);
+                    rows.push(row);
+                }
+                rows.extend(std::iter::repeat_n(vec![EMPTY; width], bottom));
+                recipe.set_rows(&rows);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn recipe() -> GridRecipe {
+        let recipe = json!({
+            "ingredientPattern": "S_,MH,T_",
+            "ingredients": {
+                "S": { "type": "item", "code": "stick" },
+                "M": { "type": "item", "code": "metal" },
+                "H": { "type": "item", "code": "hammer-*" },
+                "T": { "type": "item", "code": "shovelhead-*" },
+            },
+            "width": 2,
+            "height": 3,
+            "output": { "type": "item", "code": "shovel" },
+            "copyAttributesFrom": "T",
+        });
+        serde_json::from_value(recipe).unwrap()
+    }
+
+    #[test]
+    fn rename_key() {
+        let mut recipe = recipe();
+        GridOp::RenameKey { from: 'T', to: 'X' }.apply(&mut recipe).unwrap();
+        assert_eq!(recipe.pattern, "S_,MH,X_");
+        assert!(recipe.ingredients.contains_key(&'X'));
+        assert_eq!(recipe.rest["copyAttributesFrom"], "X");
+
+        let rename = GridOp::RenameKey { from: 'X', to: EMPTY }.apply(&mut recipe);
+        assert!(matches!(rename, Err(Error::ReservedIngredientKey(EMPTY))));
+        assert_eq!(recipe.pattern, "S_,MH,X_");
+    }
+
+    #[test]
+    fn set_cell_drops_unused_ingredient() {
+        let mut recipe = recipe();
+        GridOp::SetCell { row: 1, col: 0, key: EMPTY }.apply(&mut recipe).unwrap();
+        assert_eq!(recipe.pattern, "S_,_H,T_");
+        assert!(!recipe.ingredients.contains_key(&'M'));
+    }
+
+    #[test]
+    fn pad_pattern() {
+        let mut recipe = recipe();
+        GridOp::PadPattern { top: 0, bottom: 0, left: 1, right: 0 }.apply(&mut recipe).unwrap();
+        assert_eq!(recipe.pattern, "_S_,_MH,_T_");
+        assert_eq!((recipe.width, recipe.height), (3, 3));
+    }
+
+    #[test]
+    fn validate_pattern() {
+        let mut recipe = recipe();
+        assert!(recipe.validate().is_ok());
+
+        recipe.pattern = "S_,MX,T_".to_string();
+        assert!(matches!(recipe.validate(), Err(Error::MissingIngredient { key: 'X', .. })));
+
+        recipe.pattern = "S_,M_,T_".to_string();
+        assert!(matches!(recipe.validate(), Err(Error::UnusedIngredient { key: 'H', .. })));
+
+        recipe.pattern = "S_,MH,T".to_string();
+        assert!(matches!(recipe.validate(), Err(Error::PatternSizeMismatch { .. })));
+
+        recipe.pattern = "S_,MH,T_".to_string();
+        let head = recipe.ingredients.shift_remove(&'T').unwrap();
+        recipe.ingredients.insert(EMPTY, head);
+        assert!(matches!(recipe.validate(), Err(Error::ReservedIngredientKey(EMPTY))));
+    }
+
+    #[test]
+    fn transforms_skip_symmetric_patterns() {
+        let recipe = recipe();
+        let transformed = recipe.transformed(
+            &[PatternTransform::Mirror, PatternTransform::Rotate90, PatternTransform::Rotate270]
+        );
+        let patterns: Vec<_> = transformed
+            .iter()
+            .map(|recipe| (recipe.pattern.as_str(), recipe.width, recipe.height))
+            .collect();
+        assert_eq!(patterns, [("_S,HM,_T", 2, 3), ("TMS,_H_", 3, 2), ("_H_,SMT", 3, 2)]);
+
+        let mut recipe = recipe;
+        recipe.pattern = "S_S,_M_,S_S".to_string();
+        assert!(recipe.transformed(&[PatternTransform::Mirror, PatternTransform::Flip]).is_empty());
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use crate::{
+    data::{ Grammar, Lang, Manifest, Patch, PatchIterator, Template, Translations },
+    error::Error,
+};
+
+impl Grammar {
+    /// Expand the lang entries of the grammar and the template for every tag combination, and add
+    /// them to `lang`.
+    pub fn expand_lang(
+        &self,
+        template: &Template,
+        manifest: &Manifest,
+        lang: &mut Lang
+    ) -> Result<(), Error> {
+        if template.lang.is_empty() && self.lang.is_empty() {
+            return Ok(());
+        }
+
+        let tags = self.resolve_tags(&manifest.static_props)?;
+        for patch in PatchIterator::new(&tags) {
+            for (locale, entries) in template.lang.iter().chain(&self.lang) {
+                let translations = match manifest.translations.get(locale) {
+                    Some(Translations::Table(table)) => Some(table),
+                    _ => None,
+                };
+                for (key, value) in entries {
+                    let key = render(key, &patch, None)?;
+                    let value = render(value, &patch, translations)?;
+                    insert(lang, locale, key, value)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Merge the entries of `other` into `lang`.
+pub fn merge(lang: &mut Lang, other: Lang) -> Result<(), Error> {
+    for (locale, entries) in other {
+        for (key, value) in entries {
+            insert(lang, &locale, key, value)?;
+        }
+    }
+    Ok(())
+}
+
+/// Add an entry to `lang`. Adding an entry that already exists with the same value does nothing,
+/// while adding one with a different value is an error.
+fn insert(lang: &mut Lang, locale: &str, key: String, value: String) -> Result<(), Error> {
+    let entries = lang.entry(locale.to_string()).or_default();
+    match entries.get(&key) {
+        Some(existing) if *existing != value => {
+            Err(Error::ConflictingLangEntry {
+                locale: locale.to_string(),
+                key,
+                first: existing.clone(),
+                second: value,
+            })
+        }
+        Some(_) => Ok(()),
+        None => {
+            entries.insert(key, value);
+            Ok(())
+        }
+    }
+}
+
+/// Replace the tag placeholders in `text` with their values for the current patch, applying any
+/// filters, such as `%metal|title%`. Values found in `translations` are translated before the
+/// filters are applied. Placeholders for tags the patch doesn't have are kept as is.
+pub fn render(
+    text: &str,
+    patch: &Patch,
+    translations: Option<&HashMap<String, String>>
+) -> Result<String, Error> {
+    let mut rendered = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('%') {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('%') else {
+            rest = &rest[start..];
+            break;
+        };
+
+        let placeholder = &after[..end];
+        let mut parts = placeholder.split('|');
+        let name = parts.next().unwrap_or_default();
+        match patch.iter().find(|substitution| substitution.target == name) {
+            Some(substitution) => {
+                let mut value = translations
+                    .and_then(|translations| translations.get(substitution.value))
+                    .map_or(substitution.value, String::as_str)
+                    .to_string();
+                for filter in parts {
+                    value = apply_filter(filter.trim(), &value)?;
+                }
+                rendered.push_str(&value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                // Keep the `%` and look for a placeholder starting at the next one.
+                rendered.push('%');
+                rest = after;
+            }
+        }
+    }
+
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// Apply a named filter to a tag value.
+fn apply_filter(filter: &str, value: &str) -> Result<String, Error> {
+    let capitalize = |word: &str| {
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => String::new(),
+        }
+    };
+
+    match filter {
+        "upper" => Ok(value.to_uppercase()),
+        "lower" => Ok(value.to_lowercase()),
+        "capitalize" => Ok(capitalize(value)),
+        "title" =>
+            Ok(
+                value
+                    .split(' ')
+                    .map(capitalize)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+        _ => Err(Error::UnknownFilter(filter.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Substitution;
+
+    #[test]
+    fn render_placeholders() {
+        let patch = vec![
+            Substitution { target: "metal", value: "tin bronze" },
+            Substitution { target: "glass", value: "green" }
+        ];
+
+        assert_eq!(
+            render("%metal|title% Shovel (%glass|upper%), 100%", &patch, None).unwrap(),
+            "Tin Bronze Shovel (GREEN), 100%"
+        );
+        assert_eq!(render("%unknown% %glass%", &patch, None).unwrap(), "%unknown% green");
+        assert!(render("%metal|shout%", &patch, None).is_err());
+
+        let translations = HashMap::from([("tin bronze".to_string(), "zinnbronze".to_string())]);
+        assert_eq!(
+            render("%metal|capitalize%schaufel (%glass%)", &patch, Some(&translations)).unwrap(),
+            "Zinnbronzeschaufel (green)"
+        );
+    }
+}
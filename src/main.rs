@@ -1,20 +1,28 @@
-use std::{ io::{ Seek, Write }, path::PathBuf, time::Instant };
+use std::{ path::PathBuf, time::Instant };
 
 use clap::Parser;
+use indexmap::IndexMap;
 use file::ManifestIter;
 
+mod assets;
 mod cli;
 mod data;
 mod datagen;
 mod file;
 mod error;
+mod grid;
+mod lang;
+mod output;
+mod package;
+mod recipe;
+mod voxel;
 
 fn main() -> Result<(), error::Error> {
     let time_now = Instant::now();
 
     let args = cli::Args::parse();
 
-    if args.paths().is_empty() {
+    if args.paths().is_empty() && args.command().is_none() {
         eprintln!("No input files or directories specified.");
         std::process::exit(1);
     }
@@ -23,16 +31,67 @@ fn main() -> Result<(), error::Error> {
     let dist_path = PathBuf::from(dist_path);
     std::fs::create_dir_all(&dist_path).map_err(error::Error::Io)?;
 
+    // Index the items and blocks that generated codes are checked against.
+    let mut assets = data::AssetIndex::default();
+    assets.load(args.assets())?;
+    if !args.assets().is_empty() {
+        println!("Indexed {} items and {} blocks", assets.items.len(), assets.blocks.len());
+    }
+
+    // Lang entries are merged across manifests so that each locale is written once per domain.
+    let mut langs: IndexMap<(data::Layout, Option<String>), (data::Format, data::Lang)> =
+        IndexMap::new();
+
     for manifest in args.paths().iter_manifests() {
-        let manifest = manifest?;
-        let datagen = datagen::DataGen::new(&manifest)?;
+        let mut manifest = manifest?;
+        assets.resolve_tags(&mut manifest)?;
+        let datagen = datagen::DataGen::new(&manifest);
         let recipes = datagen.generate()?;
+        let collectibles = datagen.collectibles()?;
+        let lang = datagen.lang()?;
+        let layout = args.layout().or(manifest.layout).unwrap_or(data::Layout::Flat);
+        let domain = args.domain().or(manifest.domain.as_deref());
+        let format = args.format().or(&manifest.format);
+
+        if !args.assets().is_empty() {
+            // Recipes may use the collectibles generated alongside them.
+            for (file, collectible) in &collectibles {
+                assets.insert_collectible(file, collectible, domain)?;
+            }
+            assets.validate_codes(&recipes)?;
+            assets.validate_paths(&recipes)?;
+        }
+
+        if !lang.is_empty() {
+            let (_, merged) = langs
+                .entry((layout, domain.map(str::to_owned)))
+                .or_insert_with(|| (format.clone(), data::Lang::new()));
+            lang::merge(merged, lang)?;
+        }
+
+        for (file, collectible) in collectibles {
+            let file_path = dist_path.join(output::asset_path(&file, None, layout, domain)?);
+            if args.is_dry_run() {
+                println!("Collectible ({}): {:#}", file_path.display(), collectible);
+                continue;
+            }
+
+            if let Some(parent) = file_path.parent() {
+                std::fs::create_dir_all(parent).map_err(error::Error::Io)?;
+            }
+            let file = std::fs::File::create(&file_path).map_err(error::Error::Io)?;
+            output
+                ::write_value(std::io::BufWriter::new(file), &collectible, &format)
+                .map_err(error::Error::Io)?;
+
+            println!("Saved collectible to {}", file_path.display());
+        }
 
         println!("Generated {} recipes", recipes.len());
 
         if args.is_dry_run() {
             for recipe in recipes {
-                println!("Recipe: {recipe:#?}");
+                println!("Recipe ({}): {:#}", recipe.file, recipe.recipe);
             }
             println!("Dry run complete. No files were written.");
         } else {
@@ -41,21 +100,70 @@ fn main() -> Result<(), error::Error> {
                 println!("No recipes generated.");
                 continue;
             }
-            let file_path = dist_path.join(manifest.output.as_str());
-            let file = std::fs::File::create(&file_path).map_err(error::Error::Io)?;
-            let mut writer = std::io::BufWriter::new(file);
-            writer.write_all(b"[\n").map_err(error::Error::Io)?;
+            for (file, recipes) in output::split_files(recipes, layout, domain)? {
+                let file_path = dist_path.join(file);
+                if let Some(parent) = file_path.parent() {
+                    std::fs::create_dir_all(parent).map_err(error::Error::Io)?;
+                }
+                let file = std::fs::File::create(&file_path).map_err(error::Error::Io)?;
+                let writer = std::io::BufWriter::new(file);
+
+                // Generic assets are loaded as a single object rather than a list.
+                if let [asset] = recipes.as_slice() && asset.kind == data::RecipeKind::Generic {
+                    output::write_value(writer, &asset.recipe, &format).map_err(error::Error::Io)?;
+                    println!("Saved asset to {}", file_path.display());
+                    continue;
+                }
+
+                let mut writer = output::RecipeWriter
+                    ::new(writer, &format, &manifest.name)
+                    .map_err(error::Error::Io)?;
+
+                for recipe in recipes {
+                    writer.write(&recipe).map_err(error::Error::Io)?;
+                }
+
+                writer.finish().map_err(error::Error::Io)?;
+
+                println!("Saved recipes to {}", file_path.display());
+            }
+        }
+    }
 
-            for recipe in recipes {
-                recipe.write(&mut writer).map_err(error::Error::Io)?;
-                writer.write_all(b",\n").map_err(error::Error::Io)?;
+    for ((layout, domain), (format, lang)) in langs {
+        for (locale, entries) in lang {
+            let file_path = dist_path.join(output::lang_path(&locale, layout, domain.as_deref())?);
+
+            if args.is_dry_run() {
+                println!("Lang ({}): {} entries", file_path.display(), entries.len());
+                for (key, value) in &entries {
+                    println!("  {key}: {value}");
+                }
+                continue;
             }
 
-            // Remove the last comma and newline
-            writer.seek_relative(-2).map_err(error::Error::Io)?;
-            writer.write_all(b"]").map_err(error::Error::Io)?;
+            if let Some(parent) = file_path.parent() {
+                std::fs::create_dir_all(parent).map_err(error::Error::Io)?;
+            }
+            let value = serde_json::to_value(&entries).map_err(error::Error::Json)?;
+            let file = std::fs::File::create(&file_path).map_err(error::Error::Io)?;
+            output
+                ::write_value(std::io::BufWriter::new(file), &value, &format)
+                .map_err(error::Error::Io)?;
+
+            println!("Saved {} lang entries to {}", entries.len(), file_path.display());
+        }
+    }
 
-            println!("Saved recipes to {}", file_path.display());
+    if let Some(cli::Command::Package { config, output }) = args.command() {
+        let config = data::PackageConfig::try_from(config.as_path())?;
+        let archive = output.clone().unwrap_or_else(|| PathBuf::from(config.archive_name()));
+
+        if args.is_dry_run() {
+            println!("Dry run complete. {} was not written.", archive.display());
+        } else {
+            let count = config.package(&dist_path, &archive)?;
+            println!("Packaged {count} files into {}", archive.display());
         }
     }
 
@@ -91,11 +199,15 @@ mod tests {
         let mut buf = Vec::new();
         for manifest in &mut manifest_iter {
             let manifest = manifest.unwrap();
-            let datagen = datagen::DataGen::new(&manifest).unwrap();
+            let datagen = datagen::DataGen::new(&manifest);
             let recipes = datagen.generate().unwrap();
+            let mut writer = output::RecipeWriter
+                ::new(&mut buf, &manifest.format, &manifest.name)
+                .unwrap();
             for recipe in recipes {
-                recipe.write(&mut buf).unwrap();
+                writer.write(&recipe).unwrap();
             }
+            writer.finish().unwrap();
         }
         let elapsed = start.elapsed();
         println!("Serialization took: {elapsed:.2?}");
@@ -0,0 +1,470 @@
+use std::{ io::Write, path::PathBuf };
+
+use indexmap::IndexMap;
+use itertools::Itertools;
+use serde_json::Value;
+
+use crate::{
+    data::{ Format, Generated, Layout, LineEnding, PatchOp, PatchOperation, RecipeKind, Style },
+    error::Error,
+};
+
+impl Format {
+    /// Fill in the options that are unset with those of `fallback`.
+    pub fn or(&self, fallback: &Format) -> Format {
+        Format {
+            style: self.style.or(fallback.style),
+            indent: self.indent.or(fallback.indent),
+            tabs: self.tabs.or(fallback.tabs),
+            trailing_newline: self.trailing_newline.or(fallback.trailing_newline),
+            line_ending: self.line_ending.or(fallback.line_ending),
+            json5: self.json5.or(fallback.json5),
+            annotate: self.annotate.or(fallback.annotate),
+        }
+    }
+
+    /// The string a single level of indentation is written as.
+    fn indent_str(&self) -> String {
+        if self.tabs.unwrap_or(false) {
+            "\t".to_string()
+        } else {
+            " ".repeat(self.indent.unwrap_or(4))
+        }
+    }
+}
+
+/// Writes generated recipes to a JSON array, laid out according to a [`Format`].
+pub struct RecipeWriter<W: Write> {
+    writer: LineEndingWriter<W>,
+    style: Style,
+    indent: String,
+    trailing_newline: bool,
+    annotate: bool,
+    count: usize,
+}
+
+impl<W: Write> RecipeWriter<W> {
+    /// Create a new [`RecipeWriter`] and open the array. In JSON5 mode, the array is preceded by a
+    /// comment marking the file as generated from `source`, the name of a manifest.
+    pub fn new(writer: W, format: &Format, source: &str) -> std::io::Result<Self> {
+        let json5 = format.json5.unwrap_or(false);
+        let mut writer = Self {
+            writer: LineEndingWriter {
+                inner: writer,
+                crlf: format.line_ending == Some(LineEnding::Crlf),
+            },
+            style: format.style.unwrap_or(Style::Pretty),
+            indent: format.indent_str(),
+            trailing_newline: format.trailing_newline.unwrap_or(true),
+            annotate: json5 && format.annotate.unwrap_or(false),
+            count: 0,
+        };
+        if json5 {
+            let header = format!("Generated by vsgen from {source}, do not edit.");
+            writer.write_comment(&header, "")?;
+            writer.writer.write_all(b"\n")?;
+        }
+        writer.writer.write_all(b"[")?;
+        Ok(writer)
+    }
+
+    /// Write a recipe as the next element of the array.
+    pub fn write(&mut self, recipe: &Generated) -> std::io::Result<()> {
+        if self.count > 0 {
+            self.writer.write_all(b",")?;
+        }
+        self.writer.write_all(b"\n")?;
+
+        if self.annotate {
+            let indent = match self.style {
+                Style::Compact => String::new(),
+                Style::Pretty => self.indent.clone(),
+            };
+            self.write_comment(&describe(recipe), &indent)?;
+            self.writer.write_all(b"\n")?;
+        }
+
+        match self.style {
+            Style::Compact => {
+                serde_json::to_writer(&mut self.writer, &recipe.recipe)?;
+            }
+            Style::Pretty => {
+                let mut buf = Vec::new();
+                let formatter = serde_json::ser::PrettyFormatter::with_indent(
+                    self.indent.as_bytes()
+                );
+                let mut serializer = serde_json::Serializer::with_formatter(&mut buf, formatter);
+                serde::Serialize::serialize(&recipe.recipe, &mut serializer)?;
+
+                // Nest the recipe one level inside the array. Newlines within strings are always
+                // escaped, so every raw newline is part of the layout.
+                self.writer.write_all(self.indent.as_bytes())?;
+                for (i, line) in buf.split(|&b| b == b'\n').enumerate() {
+                    if i > 0 {
+                        self.writer.write_all(b"\n")?;
+                        self.writer.write_all(self.indent.as_bytes())?;
+                    }
+                    self.writer.write_all(line)?;
+                }
+            }
+        }
+
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Write a line comment, without a trailing newline.
+    fn write_comment(&mut self, text: &str, indent: &str) -> std::io::Result<()> {
+        // Keep the comment on one line, so it cannot end early and swallow what follows.
+        let text = text.replace(['\r', '\n'], " ");
+        write!(self.writer, "{indent}// {text}")
+    }
+
+    /// Close the array and return the underlying writer.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        if self.count > 0 {
+            self.writer.write_all(b"\n")?;
+        }
+        self.writer.write_all(b"]")?;
+        if self.trailing_newline {
+            self.writer.write_all(b"\n")?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer.inner)
+    }
+}
+
+impl Generated {
+    /// The path to write the recipe to, relative to the dist directory.
+    pub fn path(&self, layout: Layout, domain: Option<&str>) -> Result<PathBuf, Error> {
+        asset_path(&self.file, self.kind.folder(), layout, domain)
+    }
+
+    /// The asset location the game loads the recipe from, such as
+    /// `mymod:recipes/grid/tools/shovel.json`.
+    pub fn location(&self, domain: &str) -> String {
+        match self.kind.folder() {
+            Some(folder) => format!("{domain}:{folder}/{}", self.file),
+            None => format!("{domain}:{}", self.file),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Generated {
+    /// A generic asset written to `file`, generated by the default template without any tags.
+    pub fn generic(file: &str, recipe: Value) -> Self {
+        Generated {
+            kind: RecipeKind::Generic,
+            grammar: None,
+            template: "default".to_string(),
+            file: file.to_string(),
+            output: String::new(),
+            tags: Vec::new(),
+            depends_on: Vec::new(),
+            recipe,
+        }
+    }
+}
+
+/// The path to write an asset to, relative to the dist directory. With the mod layout, assets are
+/// placed in `folder` within the assets folder of the mod domain.
+pub fn asset_path(
+    file: &str,
+    folder: Option<&str>,
+    layout: Layout,
+    domain: Option<&str>
+) -> Result<PathBuf, Error> {
+    match layout {
+        Layout::Flat => Ok(PathBuf::from(file)),
+        Layout::Mod => {
+            let domain = domain.ok_or(Error::MissingDomain)?;
+            let mut path = PathBuf::from("assets").join(domain);
+            if let Some(folder) = folder {
+                path.push(folder);
+            }
+            path.push(file);
+            Ok(path)
+        }
+    }
+}
+
+/// A file next to `file`, named after it with a suffix, such as `shovel-disable.json`.
+pub fn sibling_file(file: &str, suffix: &str) -> String {
+    match file.strip_suffix(".json") {
+        Some(stem) => format!("{stem}-{suffix}.json"),
+        None => format!("{file}-{suffix}"),
+    }
+}
+
+/// The path to write the lang file for a locale to, relative to the dist directory.
+pub fn lang_path(locale: &str, layout: Layout, domain: Option<&str>) -> Result<PathBuf, Error> {
+    let file = format!("{locale}.json");
+    match layout {
+        Layout::Flat => Ok(PathBuf::from("lang").join(file)),
+        Layout::Mod => {
+            let domain = domain.ok_or(Error::MissingDomain)?;
+            Ok(PathBuf::from("assets").join(domain).join("lang").join(file))
+        }
+    }
+}
+
+/// Write a single JSON value, laid out according to a [`Format`]. In JSON5 mode, the value is
+/// preceded by a comment marking the file as generated.
+pub fn write_value<W: Write>(writer: W, value: &Value, format: &Format) -> std::io::Result<W> {
+    let mut writer = LineEndingWriter {
+        inner: writer,
+        crlf: format.line_ending == Some(LineEnding::Crlf),
+    };
+
+    if format.json5.unwrap_or(false) {
+        writer.write_all(b"// Generated by vsgen, do not edit.\n")?;
+    }
+    match format.style.unwrap_or(Style::Pretty) {
+        Style::Compact => serde_json::to_writer(&mut writer, value)?,
+        Style::Pretty => {
+            let indent = format.indent_str();
+            let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+            let mut serializer = serde_json::Serializer::with_formatter(&mut writer, formatter);
+            serde::Serialize::serialize(value, &mut serializer)?;
+        }
+    }
+    if format.trailing_newline.unwrap_or(true) {
+        writer.write_all(b"\n")?;
+    }
+
+    writer.flush()?;
+    Ok(writer.inner)
+}
+
+/// Group recipes by the path they are written to, keeping the files and the recipes within each
+/// file in the order they first appear.
+pub fn split_files(
+    recipes: Vec<Generated>,
+    layout: Layout,
+    domain: Option<&str>
+) -> Result<IndexMap<PathBuf, Vec<Generated>>, Error> {
+    let mut files = IndexMap::<PathBuf, Vec<Generated>>::new();
+    for recipe in recipes {
+        files.entry(recipe.path(layout, domain)?).or_default().push(recipe);
+    }
+
+    // Generic assets are written as a single object each, so they can't share a file.
+    for (path, recipes) in &files {
+        if recipes.len() > 1 && recipes.iter().any(|recipe| recipe.kind == RecipeKind::Generic) {
+            return Err(Error::DuplicateAssetFile(path.clone()));
+        }
+    }
+
+    // Recipes that depend on other mods are written disabled, and enabled by a patch that only
+    // applies when those mods are installed.
+    let mut enables = Vec::new();
+    for recipes in files.values_mut() {
+        for (index, recipe) in recipes.iter_mut().enumerate() {
+            if recipe.depends_on.is_empty() {
+                continue;
+            }
+            let domain = domain.ok_or(Error::MissingDependsOnDomain)?;
+            if let Value::Object(fields) = &mut recipe.recipe {
+                fields.insert("enabled".to_string(), Value::Bool(false));
+            }
+
+            // Generic assets are the whole file, while recipes are entries of a list.
+            let path = match recipe.kind {
+                RecipeKind::Generic => "/enabled".to_string(),
+                _ => format!("/{index}/enabled"),
+            };
+            let enable = PatchOperation {
+                op: PatchOp::Add,
+                path,
+                file: recipe.location(domain),
+                value: Some(Value::Bool(true)),
+                from_path: None,
+                depends_on: recipe.depends_on.clone(),
+                rest: Default::default(),
+            };
+            enables.push(Generated {
+                kind: RecipeKind::Patch,
+                grammar: recipe.grammar.clone(),
+                template: recipe.template.clone(),
+                file: sibling_file(&recipe.file, "enable"),
+                output: enable.file.clone(),
+                tags: recipe.tags.clone(),
+                depends_on: Vec::new(),
+                recipe: serde_json::to_value(&enable).map_err(Error::Json)?,
+            });
+        }
+    }
+    for enable in enables {
+        files.entry(enable.path(layout, domain)?).or_default().push(enable);
+    }
+
+    Ok(files)
+}
+
+/// Describe where a recipe came from: its grammar, template and tag values.
+pub fn describe(recipe: &Generated) -> String {
+    let mut description = String::new();
+    if let Some(grammar) = &recipe.grammar {
+        description.push_str(&format!("grammar: {grammar}, "));
+    }
+    description.push_str(&format!("template: {}", recipe.template));
+    if !recipe.tags.is_empty() {
+        let tags = recipe.tags
+            .iter()
+            .map(|(tag, value)| format!("{tag}={value}"))
+            .join(", ");
+        description.push_str(&format!(", tags: {tags}"));
+    }
+    description
+}
+
+/// A writer that translates `\n` into the configured line ending.
+struct LineEndingWriter<W: Write> {
+    inner: W,
+    crlf: bool,
+}
+
+impl<W: Write> Write for LineEndingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if !self.crlf {
+            return self.inner.write(buf);
+        }
+        for (i, line) in buf.split(|&b| b == b'\n').enumerate() {
+            if i > 0 {
+                self.inner.write_all(b"\r\n")?;
+            }
+            self.inner.write_all(line)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{ data::Manifest, datagen::DataGen };
+
+    fn write(recipes: &[Generated], format: &Format) -> String {
+        let mut writer = RecipeWriter::new(Vec::new(), format, "tools/test.json").unwrap();
+        for recipe in recipes {
+            writer.write(recipe).unwrap();
+        }
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn write_formats() {
+        let recipes = [json!({ "a": [1] }), json!({ "b": "x\ny" })].map(|recipe| {
+            Generated::generic("test.json", recipe)
+        });
+
+        let pretty = Format { indent: Some(2), ..Default::default() };
+        assert_eq!(
+            write(&recipes, &pretty),
+            "[\n  {\n    \"a\": [\n      1\n    ]\n  },\n  {\n    \"b\": \"x\\ny\"\n  }\n]\n"
+        );
+
+        let compact = Format {
+            style: Some(Style::Compact),
+            trailing_newline: Some(false),
+            line_ending: Some(LineEnding::Crlf),
+            ..Default::default()
+        };
+        assert_eq!(write(&recipes, &compact), "[\r\n{\"a\":[1]},\r\n{\"b\":\"x\\ny\"}\r\n]");
+        assert_eq!(write(&[], &compact), "[]");
+    }
+
+    #[test]
+    fn write_json5_header_and_annotations() {
+        let recipe = Generated {
+            grammar: Some("tools".to_string()),
+            tags: vec![("metal".to_string(), "copper".to_string())],
+            ..Generated::generic("test.json", json!({ "a": 1 }))
+        };
+
+        let json5 = Format { indent: Some(2), json5: Some(true), ..Default::default() };
+        assert_eq!(
+            write(std::slice::from_ref(&recipe), &json5),
+            "// Generated by vsgen from tools/test.json, do not edit.\n[\n  {\n    \"a\": 1\n  }\n]\n"
+        );
+
+        let annotated = Format { annotate: Some(true), ..json5 };
+        assert_eq!(
+            write(&[recipe], &annotated),
+            concat!(
+                "// Generated by vsgen from tools/test.json, do not edit.\n[\n",
+                "  // grammar: tools, template: default, tags: metal=copper\n",
+                "  {\n    \"a\": 1\n  }\n]\n"
+            )
+        );
+    }
+
+    #[test]
+    fn enable_patches_index_sorted_recipes() {
+        let manifest: Manifest = serde_json
+            ::from_value(
+                json!({
+                "output": "tools.json",
+                "sort": "output",
+                "templates": [{
+                    "name": "default",
+                    "ingredientPattern": "#",
+                    "ingredients": { "#": { "type": "item", "code": "stick" } },
+                    "output": { "type": "item", "code": "%item%" },
+                }],
+                "grammars": [
+                    { "tags": [{ "name": "item", "values": ["zeta", "alpha"] }] },
+                    {
+                        "dependsOn": [{ "modid": "extramaterials" }],
+                        "tags": [{ "name": "item", "values": ["mid"] }],
+                    },
+                ],
+            })
+            )
+            .unwrap();
+        let datagen = DataGen::new(&manifest);
+
+        let missing = split_files(datagen.generate().unwrap(), Layout::Flat, None);
+        assert!(matches!(missing, Err(Error::MissingDependsOnDomain)));
+
+        let files = split_files(datagen.generate().unwrap(), Layout::Flat, Some("demo")).unwrap();
+        let tools = &files[Path::new("tools.json")];
+        let outputs: Vec<_> = tools.iter().map(|recipe| recipe.output.as_str()).collect();
+        assert_eq!(outputs, ["alpha", "mid", "zeta"]);
+        assert_eq!(tools[1].recipe["enabled"], json!(false));
+        assert!(tools[0].recipe.get("enabled").is_none());
+
+        let enable = &files[Path::new("tools-enable.json")];
+        assert_eq!(enable.len(), 1);
+        assert_eq!(
+            enable[0].recipe,
+            json!({
+                "op": "add",
+                "path": "/1/enabled",
+                "file": "demo:recipes/grid/tools.json",
+                "value": true,
+                "dependsOn": [{ "modid": "extramaterials" }],
+            })
+        );
+    }
+
+    #[test]
+    fn generic_assets_need_their_own_file() {
+        let asset = |file: &str| Generated::generic(file, json!({ "code": file }));
+
+        let files = split_files(vec![asset("a.json"), asset("b.json")], Layout::Flat, None);
+        assert_eq!(files.unwrap().len(), 2);
+
+        let files = split_files(vec![asset("a.json"), asset("a.json")], Layout::Flat, None);
+        assert!(matches!(files, Err(Error::DuplicateAssetFile(_))));
+    }
+}
@@ -0,0 +1,146 @@
+use std::{ io::Write, path::{ Path, PathBuf } };
+
+use zip::{ write::SimpleFileOptions, CompressionMethod, DateTime, ZipWriter };
+
+use crate::{ data::PackageConfig, error::Error };
+
+impl TryFrom<&Path> for PackageConfig {
+    type Error = Error;
+
+    fn try_from(path: &Path) -> Result<Self, Self::Error> {
+        let input = std::fs::read_to_string(path).map_err(Error::Io)?;
+        let mut config: PackageConfig = serde_json::from_str(&input).map_err(Error::Json)?;
+
+        // Included folders are relative to the config, like the paths in manifests.
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for include in &mut config.include {
+            *include = dir.join(&*include);
+        }
+        Ok(config)
+    }
+}
+
+impl PackageConfig {
+    /// The default file name of the archive, `<modid>_<version>.zip`.
+    pub fn archive_name(&self) -> String {
+        format!("{}_{}.zip", self.mod_info.modid, self.mod_info.version)
+    }
+
+    /// Write `modinfo.json` to the dist directory, then archive the dist directory and the included
+    /// folders. Returns the number of files archived.
+    ///
+    /// Entries are sorted by name and have fixed timestamps and permissions, so packaging the same
+    /// files always produces the same archive.
+    pub fn package(&self, dist: &Path, archive: &Path) -> Result<usize, Error> {
+        let mod_info = serde_json::to_string_pretty(&self.mod_info).map_err(Error::Json)?;
+        std::fs::write(dist.join("modinfo.json"), mod_info + "\n").map_err(Error::Io)?;
+
+        let mut entries = Vec::new();
+        for root in std::iter::once(dist).chain(self.include.iter().map(PathBuf::as_path)) {
+            collect_files(root, root, &mut entries)?;
+        }
+        entries.sort();
+
+        // Don't package the archive into itself when it is written inside the dist directory.
+        let archive_path = std::path::absolute(archive).map_err(Error::Io)?;
+        let mut files = Vec::with_capacity(entries.len());
+        for (name, path) in entries {
+            if std::path::absolute(&path).map_err(Error::Io)? == archive_path {
+                continue;
+            }
+            if files.last().is_some_and(|(last, _)| *last == name) {
+                return Err(Error::DuplicatePackageEntry(name));
+            }
+            files.push((name, path));
+        }
+
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .last_modified_time(DateTime::default())
+            .unix_permissions(0o644);
+
+        if let Some(parent) = archive.parent() {
+            std::fs::create_dir_all(parent).map_err(Error::Io)?;
+        }
+        let file = std::fs::File::create(archive).map_err(Error::Io)?;
+        let mut zip = ZipWriter::new(std::io::BufWriter::new(file));
+        for (name, path) in &files {
+            zip.start_file(name, options).map_err(Error::Zip)?;
+            zip.write_all(&std::fs::read(path).map_err(Error::Io)?).map_err(Error::Io)?;
+        }
+        zip.finish().map_err(Error::Zip)?.flush().map_err(Error::Io)?;
+
+        Ok(files.len())
+    }
+}
+
+/// Recursively collect the files under `dir`, named by their `/`-separated path relative to
+/// `root`.
+pub fn collect_files(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) -> Result<(), Error> {
+    for entry in std::fs::read_dir(dir).map_err(Error::Io)? {
+        let path = entry.map_err(Error::Io)?.path();
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            let name = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            files.push((name, path));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use zip::ZipArchive;
+
+    use super::*;
+
+    #[test]
+    fn package_sorted_reproducible_archive() {
+        let dir = std::env::temp_dir().join(format!("vsgen-package-{}", std::process::id()));
+        let dist = dir.join("dist");
+        let textures = dir.join("static").join("assets").join("demo").join("textures");
+        std::fs::create_dir_all(dist.join("assets").join("demo")).unwrap();
+        std::fs::create_dir_all(&textures).unwrap();
+        std::fs::write(dist.join("b.json"), "[]").unwrap();
+        std::fs::write(dist.join("assets").join("demo").join("a.json"), "[]").unwrap();
+        std::fs::write(textures.join("x.png"), []).unwrap();
+        let config_path = dir.join("vsgen.json");
+        std::fs
+            ::write(
+                &config_path,
+                r#"{ "modinfo": { "modid": "demo", "name": "Demo", "version": "1.0.0" }, "include": ["static"] }"#
+            )
+            .unwrap();
+
+        // The included folder is found next to the config rather than in the working directory,
+        // and the archive is skipped when packaging again into the dist directory.
+        let config = PackageConfig::try_from(config_path.as_path()).unwrap();
+        let archive = dist.join(config.archive_name());
+        let first = config.package(&dist, &archive).map(|_| std::fs::read(&archive).unwrap());
+        let second = config.package(&dist, &archive).map(|_| std::fs::read(&archive).unwrap());
+
+        std::fs::write(dir.join("static").join("b.json"), "[]").unwrap();
+        let duplicate = config.package(&dist, &archive);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert_eq!(first, second);
+        let mut zip = ZipArchive::new(std::io::Cursor::new(first)).unwrap();
+        let names: Vec<_> = zip.file_names().map(str::to_string).collect();
+        assert_eq!(names, [
+            "assets/demo/a.json",
+            "assets/demo/textures/x.png",
+            "b.json",
+            "modinfo.json",
+        ]);
+        for i in 0..zip.len() {
+            assert_eq!(zip.by_index(i).unwrap().last_modified(), Some(DateTime::default()));
+        }
+        assert!(matches!(duplicate, Err(Error::DuplicatePackageEntry(name)) if name == "b.json"));
+    }
+}
@@ -0,0 +1,316 @@
+use serde_json::Value;
+
+use crate::{
+    data::{
+        AlloyRecipe,
+        BarrelRecipe,
+        CookingRecipe,
+        DotPath,
+        PatchOp,
+        PatchOperation,
+        Recipe,
+        RecipeKind,
+        VoxelRecipe,
+    },
+    error::Error,
+};
+
+/// The character used for a filled voxel in a voxel pattern.
+pub const FILLED: char = '#';
+
+impl Recipe {
+    /// Deserialize a recipe of the given kind from a JSON value.
+    pub fn from_value(kind: RecipeKind, value: Value) -> Result<Self, Error> {
+        let recipe = match kind {
+            RecipeKind::Grid => Recipe::Grid(serde_json::from_value(value).map_err(Error::Json)?),
+            RecipeKind::Smithing | RecipeKind::Knapping | RecipeKind::Clayforming =>
+                Recipe::Voxel(serde_json::from_value(value).map_err(Error::Json)?),
+            RecipeKind::Barrel => Recipe::Barrel(serde_json::from_value(value).map_err(Error::Json)?),
+            RecipeKind::Cooking =>
+                Recipe::Cooking(serde_json::from_value(value).map_err(Error::Json)?),
+            RecipeKind::Alloy => Recipe::Alloy(serde_json::from_value(value).map_err(Error::Json)?),
+            RecipeKind::Generic => Recipe::Generic(value),
+            RecipeKind::Patch => {
+                let mut patch: PatchOperation = serde_json::from_value(value).map_err(Error::Json)?;
+                patch.path = to_pointer(&patch.path);
+                patch.from_path = patch.from_path.as_deref().map(to_pointer);
+                Recipe::Patch(patch)
+            }
+        };
+        Ok(recipe)
+    }
+
+    /// The code that identifies the recipe in error messages: the output code, the recipe code for
+    /// cooking recipes and generic assets, or the patched file for patches.
+    pub fn output_code(&self) -> &str {
+        match self {
+            Recipe::Grid(recipe) => &recipe.output.code,
+            Recipe::Voxel(recipe) => &recipe.output.code,
+            Recipe::Barrel(recipe) => &recipe.output.code,
+            Recipe::Cooking(recipe) => &recipe.code,
+            Recipe::Alloy(recipe) => &recipe.output.code,
+            Recipe::Generic(value) => value.get("code").and_then(Value::as_str).unwrap_or_default(),
+            Recipe::Patch(patch) => &patch.file,
+        }
+    }
+
+    /// Check that the recipe is one the game will accept.
+    pub fn validate(&self, kind: RecipeKind) -> Result<(), Error> {
+        let reason = match self {
+            Recipe::Grid(recipe) => {
+                return recipe.validate();
+            }
+            Recipe::Voxel(recipe) => recipe.validate(kind),
+            Recipe::Barrel(recipe) => recipe.validate(),
+            Recipe::Cooking(recipe) => recipe.validate(),
+            Recipe::Alloy(recipe) => recipe.validate(),
+            Recipe::Generic(_) => None,
+            Recipe::Patch(patch) => patch.validate(),
+        };
+
+        match reason {
+            Some(reason) => Err(Error::InvalidRecipe { output: self.output_code().to_string(), reason }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl RecipeKind {
+    /// The folder of a mod's assets that recipes of this kind are loaded from. Generic assets have
+    /// no fixed folder.
+    pub fn folder(&self) -> Option<&'static str> {
+        match self {
+            RecipeKind::Grid => Some("recipes/grid"),
+            RecipeKind::Smithing => Some("recipes/smithing"),
+            RecipeKind::Knapping => Some("recipes/knapping"),
+            RecipeKind::Clayforming => Some("recipes/clayforming"),
+            RecipeKind::Barrel => Some("recipes/barrel"),
+            RecipeKind::Cooking => Some("recipes/cooking"),
+            RecipeKind::Alloy => Some("recipes/alloy"),
+            RecipeKind::Generic => None,
+            RecipeKind::Patch => Some("patches"),
+        }
+    }
+
+    /// The maximum number of layers, rows and columns of a voxel pattern for this kind.
+    pub fn voxel_bounds(&self) -> Option<(usize, usize, usize)> {
+        match self {
+            RecipeKind::Smithing => Some((6, 16, 16)),
+            RecipeKind::Knapping => Some((1, 10, 10)),
+            RecipeKind::Clayforming => Some((16, 16, 16)),
+            _ => None,
+        }
+    }
+}
+
+impl VoxelRecipe {
+    /// Check that the pattern fits the work surface of the recipe kind and only contains `#` and
+    /// `_`. Returns the reason the recipe is invalid, if any.
+    fn validate(&self, kind: RecipeKind) -> Option<String> {
+        let (max_layers, max_rows, max_cols) = kind.voxel_bounds()?;
+
+        if self.pattern.is_empty() || self.pattern.len() > max_layers {
+            return Some(
+                format!("expected 1 to {max_layers} layers, found {}", self.pattern.len())
+            );
+        }
+
+        for (layer, rows) in self.pattern.iter().enumerate() {
+            if rows.len() > max_rows {
+                return Some(
+                    format!("layer {layer} has {} rows, but at most {max_rows} fit", rows.len())
+                );
+            }
+            for (row, cells) in rows.iter().enumerate() {
+                if cells.chars().count() > max_cols {
+                    return Some(
+                        format!("layer {layer} row {row} is wider than {max_cols} voxels")
+                    );
+                }
+                if let Some(cell) = cells.chars().find(|&c| c != FILLED && c != '_') {
+                    return Some(format!("layer {layer} row {row} contains invalid voxel {cell}"));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl BarrelRecipe {
+    /// Check the seal time and ingredient count. Returns the reason the recipe is invalid, if any.
+    fn validate(&self) -> Option<String> {
+        if self.seal_hours.is_nan() || self.seal_hours < 0.0 {
+            return Some(format!("sealHours must not be negative, found {}", self.seal_hours));
+        }
+        if self.ingredients.is_empty() || self.ingredients.len() > 2 {
+            return Some(
+                format!("expected 1 or 2 ingredients, found {}", self.ingredients.len())
+            );
+        }
+        for ingredient in &self.ingredients {
+            if
+                let Some(litres) = ingredient.rest.get("litres") &&
+                !litres.as_f64().is_some_and(|litres| litres > 0.0)
+            {
+                return Some(format!("ingredient {} must have positive litres", ingredient.code));
+            }
+        }
+        None
+    }
+}
+
+impl CookingRecipe {
+    /// Check that every slot has a valid stack and a sensible quantity range. Returns the reason the
+    /// recipe is invalid, if any.
+    fn validate(&self) -> Option<String> {
+        if self.ingredients.is_empty() {
+            return Some("expected at least one ingredient".to_string());
+        }
+        for ingredient in &self.ingredients {
+            if ingredient.valid_stacks.is_empty() {
+                return Some(format!("ingredient {} has no valid stacks", ingredient.code));
+            }
+            if ingredient.min_quantity > ingredient.max_quantity {
+                return Some(
+                    format!(
+                        "ingredient {} has minQuantity {} above maxQuantity {}",
+                        ingredient.code,
+                        ingredient.min_quantity,
+                        ingredient.max_quantity
+                    )
+                );
+            }
+        }
+        None
+    }
+}
+
+impl AlloyRecipe {
+    /// Check that every ratio range lies within `0..=1` and that the ranges can add up to a whole
+    /// melt. Returns the reason the recipe is invalid, if any.
+    fn validate(&self) -> Option<String> {
+        if self.ingredients.len() < 2 {
+            return Some(
+                format!("expected at least 2 ingredients, found {}", self.ingredients.len())
+            );
+        }
+        for ingredient in &self.ingredients {
+            let range = ingredient.minratio..=ingredient.maxratio;
+            if
+                !(0.0..=1.0).contains(range.start()) ||
+                !(0.0..=1.0).contains(range.end()) ||
+                range.is_empty()
+            {
+                return Some(
+                    format!(
+                        "ingredient {} has invalid ratio range {}..={}",
+                        ingredient.code,
+                        ingredient.minratio,
+                        ingredient.maxratio
+                    )
+                );
+            }
+        }
+
+        // Allow for floating point error when summing the ratios.
+        const EPSILON: f64 = 1e-6;
+        let min: f64 = self.ingredients.iter().map(|ingredient| ingredient.minratio).sum();
+        let max: f64 = self.ingredients.iter().map(|ingredient| ingredient.maxratio).sum();
+        if min > 1.0 + EPSILON || max < 1.0 - EPSILON {
+            return Some(
+                format!("ratios cannot add up to 1, as they range from {min} to {max} in total")
+            );
+        }
+        None
+    }
+}
+
+impl PatchOperation {
+    /// Check that the operation has what it needs to be applied. Returns the reason the patch is
+    /// invalid, if any.
+    fn validate(&self) -> Option<String> {
+        if self.file.is_empty() {
+            return Some("expected a file to patch".to_string());
+        }
+        match self.op {
+            PatchOp::Add | PatchOp::AddEach | PatchOp::AddMerge | PatchOp::Replace => {
+                if self.value.is_none() {
+                    return Some(format!("{} of {} requires a value", self.op, self.path));
+                }
+            }
+            PatchOp::Copy | PatchOp::Move => {
+                if self.from_path.is_none() {
+                    return Some(format!("{} to {} requires a fromPath", self.op, self.path));
+                }
+            }
+            PatchOp::Remove => (),
+        }
+        if self.op == PatchOp::AddEach && !self.value.as_ref().is_some_and(Value::is_array) {
+            return Some(format!("addeach to {} requires an array value", self.path));
+        }
+        None
+    }
+}
+
+/// Convert a dot path to a JSON Pointer, leaving paths that already are one untouched.
+fn to_pointer(path: &str) -> String {
+    if path.starts_with('/') { path.to_string() } else { DotPath(path.to_string()).to_pointer() }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn validate_alloy_ratios() {
+        let alloy = |tin: f64| {
+            let recipe = json!({
+                "ingredients": [
+                    { "type": "item", "code": "copper", "minratio": 0.88, "maxratio": 0.92 },
+                    { "type": "item", "code": "tin", "minratio": tin, "maxratio": 0.12 },
+                ],
+                "output": { "type": "item", "code": "tinbronze" },
+            });
+            Recipe::from_value(RecipeKind::Alloy, recipe).unwrap()
+        };
+
+        assert!(alloy(0.08).validate(RecipeKind::Alloy).is_ok());
+        assert!(alloy(0.2).validate(RecipeKind::Alloy).is_err());
+    }
+
+    #[test]
+    fn validate_voxel_bounds() {
+        let recipe = json!({
+            "ingredient": { "type": "item", "code": "flint" },
+            "pattern": [["#".repeat(11)]],
+            "output": { "type": "item", "code": "axehead" },
+        });
+        let recipe = Recipe::from_value(RecipeKind::Knapping, recipe).unwrap();
+
+        assert!(recipe.validate(RecipeKind::Knapping).is_err());
+        assert!(recipe.validate(RecipeKind::Smithing).is_ok());
+    }
+
+    #[test]
+    fn patch_paths_become_pointers() {
+        let patch = json!({
+            "op": "move",
+            "file": "game:recipes/grid/tool/shovel.json",
+            "path": "ingredients.S",
+            "fromPath": "/ingredients/a~b",
+        });
+        let Recipe::Patch(patch) = Recipe::from_value(RecipeKind::Patch, patch).unwrap() else {
+            panic!("expected a patch");
+        };
+
+        assert_eq!(patch.path, "/ingredients/S");
+        assert_eq!(patch.from_path.as_deref(), Some("/ingredients/a~b"));
+
+        let replace = json!({ "op": "replace", "file": "game:x.json", "path": "output.code" });
+        let replace = Recipe::from_value(RecipeKind::Patch, replace).unwrap();
+        assert!(replace.validate(RecipeKind::Patch).is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{
+    data::{ VoxelOp, VoxelRecipe },
+    error::Error,
+    grid::EMPTY,
+    recipe::FILLED,
+};
+
+/// A layer of a voxel pattern as rows of cells.
+type Layer = Vec<Vec<char>>;
+
+/// Split a layer of a voxel pattern into rows of cells.
+fn parse_layer(rows: &[String]) -> Layer {
+    rows.iter()
+        .map(|row| row.chars().collect())
+        .collect()
+}
+
+/// Join rows of cells back into a layer of a voxel pattern.
+fn format_layer(layer: &Layer) -> Vec<String> {
+    layer
+        .iter()
+        .map(|row| row.iter().collect())
+        .collect()
+}
+
+/// Check whether a layer is selected, where no selection means every layer.
+fn is_selected(layers: &Option<Vec<usize>>, layer: usize) -> bool {
+    layers.as_ref().is_none_or(|layers| layers.contains(&layer))
+}
+
+impl VoxelOp {
+    /// Apply the operation to the pattern of a voxel recipe.
+    pub fn apply(&self, recipe: &mut VoxelRecipe) -> Result<(), Error> {
+        match self {
+            VoxelOp::Transform { transform, layers } => {
+                for (index, rows) in recipe.pattern.iter_mut().enumerate() {
+                    if is_selected(layers, index) {
+                        *rows = format_layer(&transform.apply(&parse_layer(rows)));
+                    }
+                }
+            }
+            VoxelOp::Translate { x, z, layers } => {
+                for (index, rows) in recipe.pattern.iter_mut().enumerate() {
+                    if !is_selected(layers, index) {
+                        continue;
+                    }
+                    let layer = parse_layer(rows);
+                    let shifted = translate(&layer, *x, *z).ok_or_else(|| Error::VoxelOutOfBounds {
+                        output: recipe.output.code.clone(),
+                        layer: index,
+                        x: *x,
+                        z: *z,
+                    })?;
+                    *rows = format_layer(&shifted);
+                }
+            }
+            VoxelOp::Union { pattern } => {
+                combine(&mut recipe.pattern, pattern, FILLED);
+            }
+            VoxelOp::Subtract { pattern } => {
+                combine(&mut recipe.pattern, pattern, EMPTY);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Shift a layer by `x` columns and `z` rows, keeping its size. Returns `None` if a filled voxel
+/// would be moved off the layer.
+fn translate(layer: &Layer, x: isize, z: isize) -> Option<Layer> {
+    let height = layer.len();
+    let width = layer.iter().map(Vec::len).max().unwrap_or_default();
+    let mut shifted = vec![vec![EMPTY; width]; height];
+
+    for (row, cells) in layer.iter().enumerate() {
+        for (col, &cell) in cells.iter().enumerate() {
+            if cell != FILLED {
+                continue;
+            }
+            let target_row = row.checked_add_signed(z).filter(|&row| row < height)?;
+            let target_col = col.checked_add_signed(x).filter(|&col| col < width)?;
+            shifted[target_row][target_col] = FILLED;
+        }
+    }
+
+    Some(shifted)
+}
+
+/// Set every voxel that is filled in `other` to `cell`, growing `pattern` to fit `other`.
+fn combine(pattern: &mut Vec<Vec<String>>, other: &[Vec<String>], cell: char) {
+    if pattern.len() < other.len() {
+        pattern.resize(other.len(), Vec::new());
+    }
+
+    for (rows, other) in pattern.iter_mut().zip(other) {
+        let mut layer = parse_layer(rows);
+        for (row, other_cells) in other.iter().enumerate() {
+            if layer.len() <= row {
+                layer.resize(row + 1, Vec::new());
+            }
+            for (col, other_cell) in other_cells.chars().enumerate() {
+                if other_cell != FILLED {
+                    continue;
+                }
+                let cells = &mut layer[row];
+                if cells.len() <= col {
+                    cells.resize(col + 1, EMPTY);
+                }
+                cells[col] = cell;
+            }
+        }
+
+        // Keep the layer rectangular after growing some of its rows.
+        let width = layer.iter().map(Vec::len).max().unwrap_or_default();
+        for cells in &mut layer {
+            cells.resize(width, EMPTY);
+        }
+        *rows = format_layer(&layer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(rows: &[&str]) -> Layer {
+        rows.iter()
+            .map(|row| row.chars().collect())
+            .collect()
+    }
+
+    #[test]
+    fn translate_layer() {
+        let shifted = translate(&layer(&["#__", "##_"]), 1, 0).unwrap();
+        assert_eq!(shifted, layer(&["_#_", "_##"]));
+        assert!(translate(&layer(&["#__", "##_"]), 0, 1).is_none());
+    }
+
+    #[test]
+    fn union_and_subtract() {
+        let mut pattern = vec![vec!["#_".to_string()]];
+        combine(&mut pattern, &[vec!["_#".to_string(), "#".to_string()]], FILLED);
+        assert_eq!(pattern, [["##", "#_"]]);
+        combine(&mut pattern, &[vec!["#_".to_string()]], EMPTY);
+        assert_eq!(pattern, [["_#", "#_"]]);
+    }
+}